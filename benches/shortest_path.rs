@@ -1,20 +1,25 @@
-use mappy::{Map, Spot};
+use mappy::{Map, PathAlgorithm, Spot};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 pub const MAP_WIDTH: usize = 80;
 pub const MAP_HEIGHT: usize = 80;
 
 fn make_map(start: &(usize, usize), end: &(usize, usize)) -> Map<char, char> {
-    let mut map = Map::new("map", MAP_WIDTH, MAP_HEIGHT, &|_| '.');
-    let mut rng = rand::thread_rng();
+    make_sized_map(&mut rand::thread_rng(), MAP_WIDTH, MAP_HEIGHT, start, end)
+}
+
+// Same density of walls as the 80x80 map (200 walls) whatever the size.
+fn make_sized_map<R: Rng>(rng: &mut R, width: usize, height: usize, start: &(usize, usize), end: &(usize, usize)) -> Map<char, char> {
+    let mut map = Map::new("map", width, height, &|_| '.');
 
     // Add random walls
-    let n_walls = 200;
+    let n_walls = 200 * width * height / (MAP_WIDTH * MAP_HEIGHT);
     for _ in 0..n_walls {
         let target = (
-            rng.gen_range(0..MAP_WIDTH as usize),
-            rng.gen_range(0..MAP_HEIGHT as usize)
+            rng.gen_range(0..width),
+            rng.gen_range(0..height)
         );
         if &target != start && &target != end {
             map.set(&target, Spot::new('#', None));
@@ -38,5 +43,24 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
-criterion_main!(benches);
\ No newline at end of file
+// Map is built once outside of the timing so only the search is measured.
+pub fn compare_benchmark(c: &mut Criterion) {
+    let available = |tile: &char| if tile == &'.' { 1 } else { 0 };
+
+    for size in [80, 1000] {
+        let (start, end) = ((1, size - 1), (size - 3, 0));
+        let map = make_sized_map(&mut StdRng::seed_from_u64(42), size, size, &start, &end);
+        let mut group = c.benchmark_group(format!("shortest_path_{}x{}", size, size));
+        group.sample_size(10);
+
+        for (name, algorithm) in [("a_star", PathAlgorithm::ASTAR), ("jps", PathAlgorithm::JPS)] {
+            group.bench_function(name, |b| {
+                b.iter(|| black_box(map.shortest_path_using(algorithm, &start, &end, &available)))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, criterion_benchmark, compare_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,300 @@
+//! Text format for maps which can be edited by hand and diffed.  Sections start with a header
+//! line and blank lines are ignored (in the map section only empty ones since a row of spaces
+//! is a row of tiles):
+//!
+//! ```text
+//! [legend]
+//! # wall
+//! . floor
+//! [map]
+//! #####
+//! #...#
+//! #####
+//! [items]
+//! 3,1 20 gold coin
+//! [rooms]
+//! 0,0 4 2
+//! ```
+//!
+//! Legend lines are a glyph, a space and a name which the caller turns into a tile.  Items are
+//! the location, a count and the item.  Rooms are the same arguments as Rectangle::new.  Only
+//! the map section is required.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::{Error, Map, Rectangle, Spot};
+
+const LEGEND: &str = "[legend]";
+const MAP: &str = "[map]";
+const ITEMS: &str = "[items]";
+const ROOMS: &str = "[rooms]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Start, Legend, Map, Items, Rooms
+}
+
+/// Parse a map.  tile_fn is given each glyph along with its name from the legend (or an empty
+/// name if there is no legend).  Errors name the line and column (both 1 based) of the problem.
+pub fn read_ascii_map<T: PartialEq, I: Default + PartialEq, S: Into<String>>(name: S, text: &str,
+                                                                           tile_fn: &dyn Fn(char, &str) -> Option<T>,
+                                                                           item_fn: &dyn Fn(&str) -> Option<I>) -> Result<Map<T, I>, Error> {
+    let mut section = Section::Start;
+    let mut legend: BTreeMap<char, String> = BTreeMap::new();
+    let mut rows: Vec<(usize, &str)> = vec![];
+    let mut items: Vec<(usize, &str)> = vec![];
+    let mut rooms: Vec<(usize, &str)> = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        section = match line.trim_end() {
+            LEGEND => Section::Legend,
+            MAP => Section::Map,
+            ITEMS => Section::Items,
+            ROOMS => Section::Rooms,
+            "" if line.is_empty() || section != Section::Map => continue,
+            _ => {
+                match section {
+                    Section::Start => return Err(malformed(line_number, 1, "expected section header")),
+                    Section::Legend => {
+                        let mut chars = line.chars();
+                        let glyph = chars.next().unwrap();
+                        if chars.next() != Some(' ') {
+                            return Err(malformed(line_number, 2, "expected space after glyph"))
+                        }
+                        legend.insert(glyph, chars.as_str().trim().to_string());
+                    }
+                    Section::Map => rows.push((line_number, line)),
+                    Section::Items => items.push((line_number, line)),
+                    Section::Rooms => rooms.push((line_number, line)),
+                }
+                continue
+            }
+        }
+    }
+
+    let end = text.lines().count() + 1;
+    let (first_line, first_row) = rows.first().ok_or_else(|| malformed(end, 1, "missing [map] section"))?;
+    let width = first_row.chars().count();
+    let height = rows.len();
+
+    for (line_number, row) in rows.iter() {
+        for (column, glyph) in row.chars().enumerate() {
+            let name = if legend.is_empty() {
+                ""
+            } else {
+                legend.get(&glyph).ok_or_else(|| malformed(*line_number, column + 1, "glyph not in legend"))?
+            };
+
+            if tile_fn(glyph, name).is_none() {
+                return Err(malformed(*line_number, column + 1, "unknown tile"))
+            }
+        }
+
+        if row.chars().count() != width {
+            return Err(malformed(*line_number, width.min(row.chars().count()) + 1,
+                                 &format!("row width differs from line {}", first_line)))
+        }
+    }
+
+    let grid: Vec<Vec<char>> = rows.iter().map(|(_, row)| row.chars().collect()).collect();
+    let default_fn = |(x, y): (usize, usize)| {
+        let glyph = grid[y][x];
+        let name = legend.get(&glyph).map(|name| name.as_str()).unwrap_or("");
+        tile_fn(glyph, name).unwrap()
+    };
+    let mut map = Map::new(name, width, height, &default_fn);
+
+    for (line_number, line) in items {
+        let (loc, rest) = parse_loc(line_number, line)?;
+        let (count, item) = rest.trim_start().split_once(' ')
+            .ok_or_else(|| malformed(line_number, line.len() + 1, "expected count and item"))?;
+        let count = count.parse::<usize>().map_err(|_| malformed(line_number, column_of(line, count), "invalid count"))?;
+        let item = item_fn(item.trim()).ok_or_else(|| malformed(line_number, column_of(line, item), "unknown item"))?;
+        let spot: &mut Spot<T, I> = map.get_mut(&loc).ok_or_else(|| malformed(line_number, 1, "location outside of map"))?;
+
+        spot.add_item((item, count));
+    }
+
+    for (line_number, line) in rooms {
+        let (loc, rest) = parse_loc(line_number, line)?;
+        let mut dimensions = rest.split_whitespace();
+        let mut dimension = || dimensions.next()
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or_else(|| malformed(line_number, column_of(line, rest), "expected width and height"));
+        let (room_width, room_height) = (dimension()?, dimension()?);
+        let room = Rectangle::new(loc.0, loc.1, room_width, room_height)
+            .map_err(|error| malformed(line_number, 1, &error.to_string()))?;
+
+        if !map.is_valid_loc(&room.lrc) {
+            return Err(malformed(line_number, 1, "room outside of map"))
+        }
+        map.add_room(room);
+    }
+
+    Ok(map)
+}
+
+/// Write a map in the format read_ascii_map understands.  tile_fn gives the glyph and legend
+/// name for a tile.  The legend is sorted by glyph so output is stable between runs.
+pub fn write_ascii_map<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, tile_fn: &dyn Fn(&T) -> (char, String),
+                                                            item_fn: &dyn Fn(&I) -> String) -> String {
+    let mut legend: BTreeMap<char, String> = BTreeMap::new();
+    let mut rows = String::new();
+    let mut items = String::new();
+
+    for ((x, y), spot) in map.iter() {
+        let (glyph, name) = tile_fn(&spot.solid);
+        legend.entry(glyph).or_insert(name);
+        rows.push(glyph);
+        if x == map.width - 1 {
+            rows.push('\n');
+        }
+
+        for (item, count) in spot.items.iter().flatten() {
+            let _ = writeln!(items, "{},{} {} {}", x, y, count, item_fn(item));
+        }
+    }
+
+    let mut text = String::new();
+    let _ = writeln!(text, "{}", LEGEND);
+    for (glyph, name) in legend {
+        let _ = writeln!(text, "{} {}", glyph, name);
+    }
+    let _ = writeln!(text, "{}", MAP);
+    text.push_str(&rows);
+
+    if !items.is_empty() {
+        let _ = writeln!(text, "{}", ITEMS);
+        text.push_str(&items);
+    }
+
+    if !map.rooms.is_empty() {
+        let _ = writeln!(text, "{}", ROOMS);
+        for room in map.rooms.iter() {
+            let _ = writeln!(text, "{},{} {} {}", room.ulc.0, room.ulc.1, room.lrc.0 - room.ulc.0, room.lrc.1 - room.ulc.1);
+        }
+    }
+
+    text
+}
+
+fn malformed(line: usize, column: usize, message: &str) -> Error {
+    Error::Malformed { line, column, message: message.to_string() }
+}
+
+// 1 based column of part which must be a slice of line.
+fn column_of(line: &str, part: &str) -> usize {
+    part.as_ptr() as usize - line.as_ptr() as usize + 1
+}
+
+fn parse_loc(line_number: usize, line: &str) -> Result<((usize, usize), &str), Error> {
+    let (loc, rest) = line.split_once(' ').unwrap_or((line, &line[line.len()..]));
+    let (x, y) = loc.split_once(',').ok_or_else(|| malformed(line_number, 1, "expected x,y"))?;
+    let x = x.parse::<usize>().map_err(|_| malformed(line_number, 1, "invalid x"))?;
+    let y = y.parse::<usize>().map_err(|_| malformed(line_number, column_of(line, loc) + loc.find(',').unwrap() + 1, "invalid y"))?;
+
+    Ok(((x, y), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ascii_map::{read_ascii_map, write_ascii_map};
+    use crate::{Error, Map};
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    enum Tile {
+        Wall, Floor, Water
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Item(String);
+
+    const TEXT: &str = "[legend]\n\
+                        # wall\n\
+                        . floor\n\
+                        ~ water\n\
+                        [map]\n\
+                        ######\n\
+                        #..~.#\n\
+                        ######\n\
+                        [items]\n\
+                        1,1 20 gold coin\n\
+                        4,1 1 sword\n\
+                        [rooms]\n\
+                        0,0 5 2\n";
+
+    fn tile_fn(_glyph: char, name: &str) -> Option<Tile> {
+        match name {
+            "wall" => Some(Tile::Wall),
+            "floor" => Some(Tile::Floor),
+            "water" => Some(Tile::Water),
+            _ => None,
+        }
+    }
+
+    fn glyph_fn(tile: &Tile) -> (char, String) {
+        match tile {
+            Tile::Wall => ('#', "wall".to_string()),
+            Tile::Floor => ('.', "floor".to_string()),
+            Tile::Water => ('~', "water".to_string()),
+        }
+    }
+
+    fn read(text: &str) -> Result<Map<Tile, Item>, Error> {
+        read_ascii_map("map", text, &tile_fn, &|item| Some(Item(item.to_string())))
+    }
+
+    #[test]
+    fn test_read() {
+        let map = read(TEXT).unwrap();
+
+        assert_eq!((map.width, map.height), (6, 3));
+        assert_eq!(map.get(&(3, 1)).unwrap().solid, Tile::Water);
+        assert_eq!(map.get(&(1, 1)).unwrap().items, Some(vec![(Item("gold coin".to_string()), 20)]));
+        assert_eq!(map.get(&(4, 1)).unwrap().items, Some(vec![(Item("sword".to_string()), 1)]));
+        assert_eq!(map.rooms.len(), 1);
+        assert_eq!((map.rooms[0].ulc, map.rooms[0].lrc), ((0, 0), (5, 2)));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let map = read(TEXT).unwrap();
+        let text = write_ascii_map(&map, &glyph_fn, &|item| item.0.clone());
+
+        assert_eq!(text, TEXT);
+    }
+
+    #[test]
+    fn test_round_trip_blank_row() {
+        let text = "[legend]\n  void\n# wall\n[map]\n###\n   \n###\n";
+        let map: Map<char, char> = read_ascii_map("map", text, &|glyph, _| Some(glyph), &|_| None).unwrap();
+
+        assert_eq!((map.width, map.height), (3, 3));
+        let name = |glyph: char| if glyph == ' ' { "void" } else { "wall" };
+        assert_eq!(write_ascii_map(&map, &|tile| (*tile, name(*tile).to_string()), &|_| String::new()), text);
+    }
+
+    #[test]
+    fn test_without_legend() {
+        let map: Map<char, char> = read_ascii_map("map", "[map]\n#.#\n", &|glyph, _| Some(glyph), &|_| None).unwrap();
+
+        assert_eq!(format!("{}", map), "#.#\n");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(read("######\n").err().unwrap().to_string(), "1:1: expected section header");
+        assert_eq!(read("[map]\n").err().unwrap().to_string(), "2:1: missing [map] section");
+        assert_eq!(read("[legend]\n#wall\n[map]\n#\n").err().unwrap().to_string(), "2:2: expected space after glyph");
+        assert_eq!(read("[legend]\n# wall\n[map]\n#.\n").err().unwrap().to_string(), "4:2: glyph not in legend");
+        assert_eq!(read("[legend]\n# wall\n. lava\n[map]\n#.\n").err().unwrap().to_string(), "5:2: unknown tile");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n#\n").err().unwrap().to_string(), "5:2: row width differs from line 4");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n[items]\n0,0 x gold\n").err().unwrap().to_string(), "6:5: invalid count");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n[items]\n0,a 1 gold\n").err().unwrap().to_string(), "6:3: invalid y");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n[items]\n5,0 1 gold\n").err().unwrap().to_string(), "6:1: location outside of map");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n[rooms]\n0,0 3\n").err().unwrap().to_string(), "6:5: expected width and height");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n[rooms]\n0,0\n").err().unwrap().to_string(), "6:4: expected width and height");
+        assert_eq!(read("[legend]\n# wall\n[map]\n##\n##\n[rooms]\n0,0 2 2\n").err().unwrap().to_string(), "7:1: room outside of map");
+    }
+}
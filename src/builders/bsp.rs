@@ -0,0 +1,160 @@
+use rand::Rng;
+use crate::{Error, Map, Rectangle};
+use crate::builders::{render_room, render_tunnel};
+
+/// Area of the map being partitioned: (x, y, width, height) in tiles.
+type Leaf = (usize, usize, usize, usize);
+
+/// Binary space partition builder.  The map is recursively split until leaves are too small
+/// to split again, a room is placed in each leaf and then sibling leaves are connected with
+/// tunnels on the way back up the tree.  Unlike RoomBuilder this leaves no large empty areas.
+pub struct BspBuilder<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a mut Map<T, I>,
+    floor_fn: &'a dyn Fn((usize, usize)) -> T,
+    wall_fn: &'a dyn Fn((usize, usize)) -> T,
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> BspBuilder<'a, T, I> {
+    pub fn new(map: &'a mut Map<T, I>, floor_fn: &'a dyn Fn((usize, usize)) -> T, wall_fn: &'a dyn Fn((usize, usize)) -> T) -> Self {
+        Self {
+            map,
+            floor_fn,
+            wall_fn,
+        }
+    }
+
+    /// Split the map into leaves no smaller than min_leaf_size on either side.  Each split
+    /// happens at a random ratio in split_ratio (e.g. (0.4, 0.6) keeps splits near the middle).
+    pub fn create<R: Rng + ?Sized>(&mut self, rng: &mut R, min_leaf_size: usize, split_ratio: (f32, f32)) -> Result<(), Error> {
+        // A room needs a wall on each side, at least one body tile, and one tile of leaf left over.
+        if min_leaf_size < 4 {
+            return Err(Error::InvalidDimensions("min_leaf_size too small (must be >= 4)".to_string()))
+        }
+
+        if min_leaf_size > self.map.width || min_leaf_size > self.map.height {
+            return Err(Error::InvalidDimensions("min_leaf_size too large".to_string()))
+        }
+
+        let (min_ratio, max_ratio) = split_ratio;
+        if !(min_ratio > 0.0 && min_ratio <= max_ratio && max_ratio < 1.0) {
+            return Err(Error::InvalidArgument("split_ratio must be within (0, 1) and min <= max".to_string()))
+        }
+
+        let mut rooms = vec![];
+        let root = (0, 0, self.map.width, self.map.height);
+        self.split(rng, root, min_leaf_size, split_ratio, &mut rooms);
+
+        for room in rooms.drain(0..) {
+            self.map.add_room(room);
+        }
+
+        Ok(())
+    }
+
+    // Returns index of a room within this subtree which siblings can tunnel to.
+    fn split<R: Rng + ?Sized>(&mut self, rng: &mut R, leaf: Leaf, min_leaf_size: usize, split_ratio: (f32, f32),
+                              rooms: &mut Vec<Rectangle>) -> usize {
+        let (x, y, width, height) = leaf;
+        let can_split_x = width >= min_leaf_size * 2;
+        let can_split_y = height >= min_leaf_size * 2;
+
+        if !can_split_x && !can_split_y {
+            return self.place_room(rng, leaf, rooms)
+        }
+
+        // Prefer cutting across the longer side so leaves stay roughly square.
+        let vertical = if !can_split_y {
+            true
+        } else if !can_split_x {
+            false
+        } else if width as f32 > height as f32 * 1.25 {
+            true
+        } else if height as f32 > width as f32 * 1.25 {
+            false
+        } else {
+            rng.gen_range(0..2) == 1
+        };
+
+        let size = if vertical { width } else { height };
+        let ratio = rng.gen_range(split_ratio.0..=split_ratio.1);
+        let cut = ((size as f32 * ratio) as usize).clamp(min_leaf_size, size - min_leaf_size);
+
+        let (first, second) = if vertical {
+            ((x, y, cut, height), (x + cut, y, width - cut, height))
+        } else {
+            ((x, y, width, cut), (x, y + cut, width, height - cut))
+        };
+
+        let first = self.split(rng, first, min_leaf_size, split_ratio, rooms);
+        let second = self.split(rng, second, min_leaf_size, split_ratio, rooms);
+
+        render_tunnel(self.map, rng, rooms[first].center(), rooms[second].center(), self.floor_fn);
+
+        if rng.gen_range(0..2) == 1 { first } else { second }
+    }
+
+    fn place_room<R: Rng + ?Sized>(&mut self, rng: &mut R, leaf: Leaf, rooms: &mut Vec<Rectangle>) -> usize {
+        let (x, y, width, height) = leaf;
+
+        // Rectangle covers x..=x + width so the largest room is one smaller than the leaf.
+        let room_width = rng.gen_range(3..width);
+        let room_height = rng.gen_range(3..height);
+        let room_x = x + rng.gen_range(0..width - room_width);
+        let room_y = y + rng.gen_range(0..height - room_height);
+        let room = Rectangle::new(room_x, room_y, room_width, room_height).unwrap();
+
+        render_room(self.map, &room, self.floor_fn, self.wall_fn);
+        rooms.push(room);
+        rooms.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::builders::BspBuilder;
+    use crate::Map;
+
+    fn build(seed: u64) -> Map<char, char> {
+        crate::builders::tests::build(80, 50, seed, &|map, rng| {
+            BspBuilder::new(map, &|_| '.', &|_| '#').create(rng, 8, (0.35, 0.65)).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_rooms_fit_and_do_not_overlap() {
+        let map = build(99);
+
+        assert!(map.rooms.len() >= 4);
+        for (i, room) in map.rooms.iter().enumerate() {
+            assert!(room.lrc.0 < map.width && room.lrc.1 < map.height);
+            for other in map.rooms.iter().skip(i + 1) {
+                assert!(!room.intersect(other));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rooms_connected() {
+        let map = build(5);
+        let available = |tile: &char| if *tile == '.' { 1 } else { 0 };
+        let start = map.rooms[0].center();
+
+        for room in map.rooms.iter().skip(1) {
+            assert!(map.shortest_path(&start, &room.center(), &available).is_some());
+        }
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut map: Map<char, char> = Map::new("map", 20, 20, &|_| ' ');
+        let mut builder = BspBuilder::new(&mut map, &|_| '.', &|_| '#');
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert!(builder.create(&mut rng, 3, (0.4, 0.6)).is_err());
+        assert!(builder.create(&mut rng, 30, (0.4, 0.6)).is_err());
+        assert!(builder.create(&mut rng, 5, (0.6, 0.4)).is_err());
+        assert!(builder.create(&mut rng, 5, (0.0, 0.6)).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use ndarray::{Array, Ix2};
+use rand::Rng;
+use crate::{add_delta, Error, Map, Rectangle, Spot};
+
+const NEIGHBORS: [(isize, isize); 8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0),           (1, 0),
+    (-1, 1),  (0, 1),  (1, 1),
+];
+
+/// Cellular automata cave builder.  The map is seeded with random walls and then smoothed
+/// with birth/survival rules so walls clump together into organic looking caves.  Only the
+/// largest connected floor region is kept so every floor tile is reachable.
+///
+/// http://www.roguebasin.com/index.php/Cellular_Automata_Method_for_Generating_Random_Cave-Like_Levels
+pub struct CaveBuilder<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a mut Map<T, I>,
+    floor_fn: &'a dyn Fn((usize, usize)) -> T,
+    wall_fn: &'a dyn Fn((usize, usize)) -> T,
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> CaveBuilder<'a, T, I> {
+    pub fn new(map: &'a mut Map<T, I>, floor_fn: &'a dyn Fn((usize, usize)) -> T, wall_fn: &'a dyn Fn((usize, usize)) -> T) -> Self {
+        Self {
+            map,
+            floor_fn,
+            wall_fn,
+        }
+    }
+
+    /// fill_ratio is the chance each tile starts as a wall.  On each iteration a floor tile
+    /// with at least birth_limit wall neighbors becomes a wall and a wall tile with fewer than
+    /// survival_limit wall neighbors becomes floor.  0.45, 5, 4 and 4 iterations is a good start.
+    /// The bounding box of the surviving cave is registered as a room.
+    pub fn create<R: Rng + ?Sized>(&mut self, rng: &mut R, fill_ratio: f32, birth_limit: usize, survival_limit: usize,
+                                   iterations: usize) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&fill_ratio) {
+            return Err(Error::InvalidArgument("fill_ratio must be between 0 and 1".to_string()))
+        }
+
+        if birth_limit > 8 || survival_limit > 8 {
+            return Err(Error::InvalidArgument("birth_limit and survival_limit must be <= 8".to_string()))
+        }
+
+        if self.map.width < 3 || self.map.height < 3 {
+            return Err(Error::InvalidDimensions("map too small for a cave".to_string()))
+        }
+
+        let (width, height) = (self.map.width, self.map.height);
+        let mut walls = Array::<bool, Ix2>::from_shape_fn((width, height), |(x, y)| {
+            Self::is_edge(x, y, width, height) || rng.gen::<f32>() < fill_ratio
+        });
+
+        for _ in 0..iterations {
+            walls = Array::<bool, Ix2>::from_shape_fn((width, height), |(x, y)| {
+                if Self::is_edge(x, y, width, height) {
+                    return true
+                }
+
+                let count = Self::wall_count(&walls, (x, y));
+                if walls[(x, y)] { count >= survival_limit } else { count >= birth_limit }
+            });
+        }
+
+        let cave = Self::largest_region(&mut walls);
+        if cave.is_empty() {
+            return Err(Error::GenerationFailed("no floor left after smoothing".to_string()))
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let loc = (x, y);
+                let tile_fn = if walls[loc] { self.wall_fn } else { self.floor_fn };
+                self.map.set(&loc, Spot::new(tile_fn(loc), None));
+            }
+        }
+
+        let (min_x, min_y) = cave.iter().fold((width, height), |(mx, my), (x, y)| (mx.min(*x), my.min(*y)));
+        let (max_x, max_y) = cave.iter().fold((0, 0), |(mx, my), (x, y)| (mx.max(*x), my.max(*y)));
+        self.map.add_room(Rectangle::new(min_x - 1, min_y - 1, max_x - min_x + 2, max_y - min_y + 2)?);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn is_edge(x: usize, y: usize, width: usize, height: usize) -> bool {
+        x == 0 || y == 0 || x == width - 1 || y == height - 1
+    }
+
+    fn wall_count(walls: &Array<bool, Ix2>, loc: (usize, usize)) -> usize {
+        NEIGHBORS.iter()
+            .filter(|delta| add_delta(&loc, delta).and_then(|n| walls.get(n).copied()).unwrap_or(true))
+            .count()
+    }
+
+    // Marks every floor tile outside of the largest region as wall and returns that region.
+    fn largest_region(walls: &mut Array<bool, Ix2>) -> Vec<(usize, usize)> {
+        let mut seen = Array::<bool, Ix2>::from_elem(walls.dim(), false);
+        let mut regions: Vec<Vec<(usize, usize)>> = vec![];
+
+        for (loc, wall) in walls.indexed_iter() {
+            if *wall || seen[loc] {
+                continue
+            }
+
+            let mut region = vec![];
+            let mut stack = vec![loc];
+            seen[loc] = true;
+            while let Some(current) = stack.pop() {
+                region.push(current);
+                for delta in NEIGHBORS.iter() {
+                    if let Some(next) = add_delta(&current, delta) {
+                        if walls.get(next) == Some(&false) && !seen[next] {
+                            seen[next] = true;
+                            stack.push(next);
+                        }
+                    }
+                }
+            }
+            regions.push(region);
+        }
+
+        let largest = regions.iter().enumerate().max_by_key(|(_, region)| region.len()).map(|(i, _)| i);
+        let mut result = vec![];
+        for (i, region) in regions.into_iter().enumerate() {
+            if Some(i) == largest {
+                result = region;
+            } else {
+                for loc in region {
+                    walls[loc] = true;
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::builders::CaveBuilder;
+    use crate::Map;
+
+    fn build(seed: u64) -> Map<char, char> {
+        crate::builders::tests::build(60, 40, seed, &|map, rng| {
+            CaveBuilder::new(map, &|_| '.', &|_| '#').create(rng, 0.45, 5, 4, 4).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_single_connected_cave() {
+        let map = build(3);
+        let available = |tile: &char| if *tile == '.' { 1 } else { 0 };
+        let floors: Vec<(usize, usize)> = map.iter()
+            .filter(|(_, tile)| tile.solid == '.')
+            .map(|(loc, _)| loc)
+            .collect();
+
+        assert!(floors.len() > 100);
+        for loc in floors.iter().step_by(37) {
+            assert!(map.shortest_path(&floors[0], loc, &available).is_some());
+        }
+    }
+
+    #[test]
+    fn test_edges_are_walls() {
+        let map = build(11);
+
+        for (loc, tile) in map.iter() {
+            if loc.0 == 0 || loc.1 == 0 || loc.0 == map.width - 1 || loc.1 == map.height - 1 {
+                assert_eq!(tile.solid, '#');
+            } else {
+                assert!(tile.solid == '.' || tile.solid == '#');
+            }
+        }
+        assert_eq!(map.rooms.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut map: Map<char, char> = Map::new("map", 20, 20, &|_| ' ');
+        let mut builder = CaveBuilder::new(&mut map, &|_| '.', &|_| '#');
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert!(builder.create(&mut rng, 1.5, 5, 4, 4).is_err());
+        assert!(builder.create(&mut rng, 0.45, 9, 4, 4).is_err());
+        assert!(builder.create(&mut rng, 1.0, 5, 4, 4).is_err());
+    }
+}
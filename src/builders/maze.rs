@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use ndarray::{Array, Ix2};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::{add_delta, Error, Map, Rectangle, Spot};
+
+const CELL_DELTAS: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MazeAlgorithm {
+    /// Depth first carving.  Long winding corridors with few branches.
+    BACKTRACKER,
+    /// Randomized Prim.  Lots of short dead ends branching off everywhere.
+    PRIM,
+    /// Randomized Kruskal.  Evenly textured with no directional bias.
+    KRUSKAL,
+    /// Eller's algorithm.  Works a row at a time so memory is proportional to width.
+    ELLER,
+}
+
+/// Maze builder which carves a perfect maze (exactly one path between any two cells).  Cells
+/// sit on odd offsets of the region with walls between them so a region 2n+1 tiles wide holds
+/// n cells across.  Braiding afterwards knocks out walls at dead ends to create loops.
+pub struct MazeBuilder<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a mut Map<T, I>,
+    floor_fn: &'a dyn Fn((usize, usize)) -> T,
+    wall_fn: &'a dyn Fn((usize, usize)) -> T,
+}
+
+// Maze in cell coordinates.  open is in tile coordinates relative to the region.
+struct Grid {
+    columns: usize,
+    rows: usize,
+    open: Array<bool, Ix2>,
+}
+
+impl Grid {
+    fn new(columns: usize, rows: usize) -> Self {
+        let open = Array::<bool, Ix2>::from_shape_fn((columns * 2 + 1, rows * 2 + 1), |(x, y)| x % 2 == 1 && y % 2 == 1);
+
+        Self { columns, rows, open }
+    }
+
+    fn cells(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+        (0..self.rows).flat_map(move |y| (0..self.columns).map(move |x| (x, y)))
+    }
+
+    fn neighbors(&self, cell: (usize, usize)) -> impl Iterator<Item=(usize, usize)> + '_ {
+        CELL_DELTAS.iter().filter_map(move |delta| {
+            let next = add_delta(&cell, delta)?;
+            if next.0 < self.columns && next.1 < self.rows { Some(next) } else { None }
+        })
+    }
+
+    #[inline]
+    fn wall_between(a: (usize, usize), b: (usize, usize)) -> (usize, usize) {
+        (a.0 + b.0 + 1, a.1 + b.1 + 1)
+    }
+
+    fn carve(&mut self, a: (usize, usize), b: (usize, usize)) {
+        self.open[Self::wall_between(a, b)] = true;
+    }
+
+    fn is_connected(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        self.open[Self::wall_between(a, b)]
+    }
+
+    fn index(&self, cell: (usize, usize)) -> usize {
+        cell.1 * self.columns + cell.0
+    }
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> MazeBuilder<'a, T, I> {
+    pub fn new(map: &'a mut Map<T, I>, floor_fn: &'a dyn Fn((usize, usize)) -> T, wall_fn: &'a dyn Fn((usize, usize)) -> T) -> Self {
+        Self {
+            map,
+            floor_fn,
+            wall_fn,
+        }
+    }
+
+    /// Carve a maze over the whole map.  braid is the fraction (0.0 - 1.0) of dead ends to
+    /// remove.  0.0 leaves a perfect maze.
+    pub fn create<R: Rng + ?Sized>(&mut self, rng: &mut R, algorithm: MazeAlgorithm, braid: f32) -> Result<(), Error> {
+        if self.map.width < 3 || self.map.height < 3 {
+            return Err(Error::InvalidDimensions("map too small for a maze".to_string()))
+        }
+
+        let region = Rectangle::new(0, 0, self.map.width - 1, self.map.height - 1)?;
+        self.create_in(rng, &region, algorithm, braid)
+    }
+
+    /// Carve a maze within region (border included).  Anything outside of region is left
+    /// alone so this can be used to fill a single room with a puzzle.  The region is
+    /// registered as a room.
+    pub fn create_in<R: Rng + ?Sized>(&mut self, rng: &mut R, region: &Rectangle, algorithm: MazeAlgorithm,
+                                      braid: f32) -> Result<(), Error> {
+        if !self.map.is_valid_loc(&region.lrc) {
+            return Err(Error::OutOfBounds { loc: region.lrc, width: self.map.width, height: self.map.height })
+        }
+
+        if !(0.0..=1.0).contains(&braid) {
+            return Err(Error::InvalidArgument("braid must be between 0 and 1".to_string()))
+        }
+
+        let columns = (region.lrc.0 - region.ulc.0) / 2;
+        let rows = (region.lrc.1 - region.ulc.1) / 2;
+        let mut grid = Grid::new(columns, rows);
+
+        match algorithm {
+            MazeAlgorithm::BACKTRACKER => Self::backtracker(rng, &mut grid),
+            MazeAlgorithm::PRIM => Self::prim(rng, &mut grid),
+            MazeAlgorithm::KRUSKAL => Self::kruskal(rng, &mut grid),
+            MazeAlgorithm::ELLER => Self::eller(rng, &mut grid),
+        }
+
+        Self::braid(rng, &mut grid, braid);
+
+        // Any leftover column or row from an even sized region stays wall.
+        for y in region.ulc.1..=region.lrc.1 {
+            for x in region.ulc.0..=region.lrc.0 {
+                let loc = (x, y);
+                let open = grid.open.get((x - region.ulc.0, y - region.ulc.1)).copied().unwrap_or(false);
+                let tile_fn = if open { self.floor_fn } else { self.wall_fn };
+                self.map.set(&loc, Spot::new(tile_fn(loc), None));
+            }
+        }
+
+        self.map.add_room(Rectangle::new(region.ulc.0, region.ulc.1, region.lrc.0 - region.ulc.0, region.lrc.1 - region.ulc.1)?);
+
+        Ok(())
+    }
+
+    fn backtracker<R: Rng + ?Sized>(rng: &mut R, grid: &mut Grid) {
+        let mut visited = vec![false; grid.columns * grid.rows];
+        let start = (rng.gen_range(0..grid.columns), rng.gen_range(0..grid.rows));
+        let mut stack = vec![start];
+        visited[grid.index(start)] = true;
+
+        while let Some(current) = stack.last().copied() {
+            let unvisited: Vec<(usize, usize)> = grid.neighbors(current)
+                .filter(|next| !visited[grid.index(*next)])
+                .collect();
+
+            if let Some(next) = unvisited.choose(rng).copied() {
+                grid.carve(current, next);
+                visited[grid.index(next)] = true;
+                stack.push(next);
+            } else {
+                stack.pop();
+            }
+        }
+    }
+
+    fn prim<R: Rng + ?Sized>(rng: &mut R, grid: &mut Grid) {
+        let mut visited = vec![false; grid.columns * grid.rows];
+        let start = (rng.gen_range(0..grid.columns), rng.gen_range(0..grid.rows));
+        let mut frontier: Vec<((usize, usize), (usize, usize))> = grid.neighbors(start).map(|next| (start, next)).collect();
+        visited[grid.index(start)] = true;
+
+        while !frontier.is_empty() {
+            let (from, to) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            if visited[grid.index(to)] {
+                continue
+            }
+
+            grid.carve(from, to);
+            visited[grid.index(to)] = true;
+            let next: Vec<((usize, usize), (usize, usize))> = grid.neighbors(to)
+                .filter(|next| !visited[grid.index(*next)])
+                .map(|next| (to, next))
+                .collect();
+            frontier.extend(next);
+        }
+    }
+
+    fn kruskal<R: Rng + ?Sized>(rng: &mut R, grid: &mut Grid) {
+        let mut sets = DisjointSet::new(grid.columns * grid.rows);
+        let mut edges: Vec<((usize, usize), (usize, usize))> = grid.cells()
+            .flat_map(|cell| {
+                let right = (cell.0 + 1 < grid.columns).then_some((cell, (cell.0 + 1, cell.1)));
+                let down = (cell.1 + 1 < grid.rows).then_some((cell, (cell.0, cell.1 + 1)));
+                right.into_iter().chain(down)
+            })
+            .collect();
+        edges.shuffle(rng);
+
+        for (a, b) in edges {
+            if sets.union(grid.index(a), grid.index(b)) {
+                grid.carve(a, b);
+            }
+        }
+    }
+
+    fn eller<R: Rng + ?Sized>(rng: &mut R, grid: &mut Grid) {
+        // 0 means the cell has not been given a set yet.
+        let mut sets = vec![0; grid.columns];
+        let mut next_set = 1;
+
+        for y in 0..grid.rows {
+            for set in sets.iter_mut().filter(|set| **set == 0) {
+                *set = next_set;
+                next_set += 1;
+            }
+
+            // Join neighbors in this row.  The last row must join everything left over.
+            let last_row = y == grid.rows - 1;
+            for x in 0..grid.columns.saturating_sub(1) {
+                if sets[x] != sets[x + 1] && (last_row || rng.gen_bool(0.5)) {
+                    grid.carve((x, y), (x + 1, y));
+                    let (keep, old) = (sets[x], sets[x + 1]);
+                    for set in sets.iter_mut().filter(|set| **set == old) {
+                        *set = keep;
+                    }
+                }
+            }
+
+            if last_row {
+                break
+            }
+
+            // Every set must continue down at least once or it would be cut off.
+            let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for (x, set) in sets.iter().enumerate() {
+                members.entry(*set).or_default().push(x);
+            }
+
+            let mut below = vec![0; grid.columns];
+            for (set, mut columns) in members {
+                columns.shuffle(rng);
+                for (i, x) in columns.into_iter().enumerate() {
+                    if i == 0 || rng.gen_bool(0.5) {
+                        grid.carve((x, y), (x, y + 1));
+                        below[x] = set;
+                    }
+                }
+            }
+            sets = below;
+        }
+    }
+
+    fn braid<R: Rng + ?Sized>(rng: &mut R, grid: &mut Grid, braid: f32) {
+        if braid <= 0.0 {
+            return
+        }
+
+        let cells: Vec<(usize, usize)> = grid.cells().collect();
+        for cell in cells {
+            let connected = grid.neighbors(cell).filter(|next| grid.is_connected(cell, *next)).count();
+            if connected != 1 || rng.gen::<f32>() >= braid {
+                continue
+            }
+
+            let closed: Vec<(usize, usize)> = grid.neighbors(cell)
+                .filter(|next| !grid.is_connected(cell, *next))
+                .collect();
+
+            // Prefer joining two dead ends so a single wall removes both.
+            let dead_ends: Vec<(usize, usize)> = closed.iter()
+                .filter(|next| grid.neighbors(**next).filter(|n| grid.is_connected(**next, *n)).count() == 1)
+                .copied()
+                .collect();
+
+            let choices = if dead_ends.is_empty() { &closed } else { &dead_ends };
+            if let Some(next) = choices.choose(rng).copied() {
+                grid.carve(cell, next);
+            }
+        }
+    }
+}
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self { parents: (0..size).collect() }
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        let mut current = index;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+
+        root
+    }
+
+    // Returns false if a and b were already in the same set.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false
+        }
+
+        self.parents[b] = a;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::builders::{MazeAlgorithm, MazeBuilder};
+    use crate::{Map, Rectangle};
+
+    const ALGORITHMS: [MazeAlgorithm; 4] = [MazeAlgorithm::BACKTRACKER, MazeAlgorithm::PRIM,
+        MazeAlgorithm::KRUSKAL, MazeAlgorithm::ELLER];
+
+    fn build(seed: u64, algorithm: MazeAlgorithm, braid: f32) -> Map<char, char> {
+        crate::builders::tests::build(21, 15, seed, &|map, rng| {
+            MazeBuilder::new(map, &|_| '.', &|_| '#').create(rng, algorithm, braid).unwrap()
+        })
+    }
+
+    fn floor_count(map: &Map<char, char>) -> usize {
+        map.iter().filter(|(_, tile)| tile.solid == '.').count()
+    }
+
+    fn dead_ends(map: &Map<char, char>) -> usize {
+        map.iter()
+            .filter(|((x, y), tile)| tile.solid == '.' && x % 2 == 1 && y % 2 == 1)
+            .filter(|(loc, _)| map.adjacent_paths(loc, &|c| *c == '.', false).count_ones() == 1)
+            .count()
+    }
+
+    #[test]
+    fn test_perfect_maze() {
+        let available = |tile: &char| if *tile == '.' { 1 } else { 0 };
+
+        for algorithm in ALGORITHMS {
+            let map = build(1, algorithm, 0.0);
+
+            // 10x7 cells plus 69 passages joining them makes a spanning tree.
+            assert_eq!(floor_count(&map), 70 + 69, "{:?}", algorithm);
+            for y in (1..map.height).step_by(2) {
+                for x in (1..map.width).step_by(2) {
+                    assert!(map.shortest_path(&(1, 1), &(x, y), &available).is_some(), "{:?}", algorithm);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_braid() {
+        for algorithm in ALGORITHMS {
+            let perfect = build(2, algorithm, 0.0);
+            let braided = build(2, algorithm, 1.0);
+
+            assert!(dead_ends(&braided) < dead_ends(&perfect), "{:?}", algorithm);
+            assert!(floor_count(&braided) > floor_count(&perfect), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_create_in() {
+        let mut map: Map<char, char> = Map::new("map", 20, 20, &|_| ' ');
+        let mut builder = MazeBuilder::new(&mut map, &|_| '.', &|_| '#');
+        let region = Rectangle::new(4, 4, 8, 6).unwrap();
+
+        builder.create_in(&mut StdRng::seed_from_u64(0), &region, MazeAlgorithm::PRIM, 0.0).unwrap();
+
+        for (loc, tile) in map.iter() {
+            let inside = loc.0 >= 4 && loc.0 <= 12 && loc.1 >= 4 && loc.1 <= 10;
+            assert_eq!(tile.solid != ' ', inside);
+        }
+        assert_eq!(map.rooms.len(), 1);
+
+        let mut builder = MazeBuilder::new(&mut map, &|_| '.', &|_| '#');
+        let outside = Rectangle::new(15, 15, 8, 8).unwrap();
+        assert!(builder.create_in(&mut StdRng::seed_from_u64(0), &outside, MazeAlgorithm::PRIM, 0.0).is_err());
+    }
+}
@@ -1,6 +1,18 @@
+//! Map generators.  Every random choice made while building (and by Rectangle::random_x,
+//! Rectangle::random_y and Map::find_random_tile_loc) comes from the rng passed in, so the same
+//! seed always gives the same result.
+
 use std::cmp::{max, min};
-use rand::{Rng, thread_rng};
-use crate::{Map, Rectangle, RectangleIteratorType, Spot};
+use rand::Rng;
+use crate::{Error, Map, Rectangle, RectangleIteratorType, Spot};
+
+mod bsp;
+mod cave;
+mod maze;
+
+pub use bsp::BspBuilder;
+pub use cave::CaveBuilder;
+pub use maze::{MazeAlgorithm, MazeBuilder};
 
 pub struct RoomBuilder<'a, T: PartialEq, I: Default + PartialEq> {
     map: &'a mut Map<T, I>,
@@ -17,15 +29,14 @@ impl<'a, T: PartialEq, I: Default + PartialEq> RoomBuilder<'a, T, I> {
         }
     }
 
-    pub fn create(&mut self, max_rooms: usize, min_size: usize, max_size: usize) -> Result<(), String>{
-        let mut rng = thread_rng();
-
+    /// Scatter up to max_rooms non-overlapping rooms and join them with tunnels.
+    pub fn create<R: Rng + ?Sized>(&mut self, rng: &mut R, max_rooms: usize, min_size: usize, max_size: usize) -> Result<(), Error> {
         if min_size < 3 {
-            return Err("min_size too small (must be >3".to_string())
+            return Err(Error::InvalidDimensions("min_size too small (must be >= 3)".to_string()))
         }
 
         if max_size > self.map.width || max_size > self.map.height {
-            return Err("max_size too large".to_string())
+            return Err(Error::InvalidDimensions("max_size too large".to_string()))
         }
 
         let mut rooms: Vec<Rectangle> = Vec::with_capacity(max_rooms);
@@ -44,16 +55,7 @@ impl<'a, T: PartialEq, I: Default + PartialEq> RoomBuilder<'a, T, I> {
         }
 
         for (i, room) in rooms.iter().skip(1).enumerate() {
-            let new_center = room.center();
-            let old_center = rooms[i].center();
-
-            if rng.gen_range(0..2) == 1 {
-                self.render_horizontal_tunnel(old_center.0, new_center.0, old_center.1);
-                self.render_vertical_tunnel(old_center.1, new_center.1, new_center.0);
-            } else {
-                self.render_vertical_tunnel(old_center.1, new_center.1, old_center.0);
-                self.render_horizontal_tunnel(old_center.0, new_center.0, new_center.1);
-            }
+            render_tunnel(self.map, rng, rooms[i].center(), room.center(), self.floor_fn);
         }
 
         for room in rooms.drain(0..) {
@@ -64,41 +66,112 @@ impl<'a, T: PartialEq, I: Default + PartialEq> RoomBuilder<'a, T, I> {
     }
 
     fn render_room(&mut self, rect: &Rectangle) {
-        for (point, point_type) in rect.iter() {
-            let tile_fn = match point_type {
-                RectangleIteratorType::BORDER => self.wall_fn,
-                RectangleIteratorType::BODY => self.floor_fn
-            };
+        render_room(self.map, rect, self.floor_fn, self.wall_fn);
+    }
+}
 
-            self.map.set(&point, Spot::new(tile_fn(point), None));
-        }
+fn render_room<T: PartialEq, I: Default + PartialEq>(map: &mut Map<T, I>, rect: &Rectangle,
+                                                    floor_fn: &dyn Fn((usize, usize)) -> T,
+                                                    wall_fn: &dyn Fn((usize, usize)) -> T) {
+    for (point, point_type) in rect.iter() {
+        let tile_fn = match point_type {
+            RectangleIteratorType::BORDER => wall_fn,
+            RectangleIteratorType::BODY => floor_fn
+        };
+
+        map.set(&point, Spot::new(tile_fn(point), None));
     }
+}
 
-    fn render_horizontal_tunnel(&mut self, start_x: usize, end_x: usize, y: usize) {
-        for x in min(start_x, end_x) ..= max(start_x, end_x) {
-            let loc = (x, y);
-            self.map.set(&loc, Spot::new((self.floor_fn)(loc), None));
-        }
+/// L-shaped tunnel between two points.  Whether we go horizontal or vertical first is random.
+fn render_tunnel<T: PartialEq, I: Default + PartialEq, R: Rng + ?Sized>(map: &mut Map<T, I>, rng: &mut R,
+                                                                        old_center: (usize, usize),
+                                                                        new_center: (usize, usize),
+                                                                        floor_fn: &dyn Fn((usize, usize)) -> T) {
+    if rng.gen_range(0..2) == 1 {
+        render_horizontal_tunnel(map, old_center.0, new_center.0, old_center.1, floor_fn);
+        render_vertical_tunnel(map, old_center.1, new_center.1, new_center.0, floor_fn);
+    } else {
+        render_vertical_tunnel(map, old_center.1, new_center.1, old_center.0, floor_fn);
+        render_horizontal_tunnel(map, old_center.0, new_center.0, new_center.1, floor_fn);
+    }
+}
+
+fn render_horizontal_tunnel<T: PartialEq, I: Default + PartialEq>(map: &mut Map<T, I>, start_x: usize, end_x: usize, y: usize,
+                                                                 floor_fn: &dyn Fn((usize, usize)) -> T) {
+    for x in min(start_x, end_x) ..= max(start_x, end_x) {
+        let loc = (x, y);
+        map.set(&loc, Spot::new((floor_fn)(loc), None));
     }
+}
 
-    fn render_vertical_tunnel(&mut self, start_y: usize, end_y: usize, x: usize) {
-        for y in min(start_y, end_y) ..= max(start_y, end_y) {
-            let loc = (x, y);
-            self.map.set(&loc, Spot::new((self.floor_fn)(loc), None));
-        }
+fn render_vertical_tunnel<T: PartialEq, I: Default + PartialEq>(map: &mut Map<T, I>, start_y: usize, end_y: usize, x: usize,
+                                                               floor_fn: &dyn Fn((usize, usize)) -> T) {
+    for y in min(start_y, end_y) ..= max(start_y, end_y) {
+        let loc = (x, y);
+        map.set(&loc, Spot::new((floor_fn)(loc), None));
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::builders::RoomBuilder;
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, SeedableRng};
+    use crate::builders::{BspBuilder, CaveBuilder, MazeAlgorithm, MazeBuilder, RoomBuilder};
     use crate::Map;
 
+    type Create = dyn Fn(&mut Map<char, char>, &mut StdRng);
+
+    // Blank width x height map built on by create with an rng seeded from seed.
+    pub(super) fn build(width: usize, height: usize, seed: u64, create: &dyn Fn(&mut Map<char, char>, &mut StdRng)) -> Map<char, char> {
+        let mut map: Map<char, char> = Map::new("map", width, height, &|_| ' ');
+
+        create(&mut map, &mut StdRng::seed_from_u64(seed));
+        map
+    }
+
+    fn tiles(map: &Map<char, char>) -> String {
+        map.iter().map(|(_, tile)| tile.solid).collect()
+    }
+
+    fn rooms(map: &Map<char, char>) -> Vec<((usize, usize), (usize, usize))> {
+        map.rooms.iter().map(|room| (room.ulc, room.lrc)).collect()
+    }
+
     #[test]
     fn test_runs() {
         let mut map: Map<char, char> = Map::new("map", 50, 50, &|_| '#');
         let mut builder = RoomBuilder::new(&mut map, &|_| '.', &|_| '#');
 
-        builder.create(7, 4, 10).unwrap();
+        builder.create(&mut thread_rng(), 7, 4, 10).unwrap();
+    }
+
+    #[test]
+    fn test_same_seed_same_map() {
+        let mut builders: Vec<(String, Box<Create>)> = vec![
+            ("rooms".to_string(), Box::new(|map: &mut Map<char, char>, rng: &mut StdRng| {
+                RoomBuilder::new(map, &|_| '.', &|_| '#').create(rng, 7, 4, 10).unwrap()
+            })),
+            ("bsp".to_string(), Box::new(|map: &mut Map<char, char>, rng: &mut StdRng| {
+                BspBuilder::new(map, &|_| '.', &|_| '#').create(rng, 8, (0.35, 0.65)).unwrap()
+            })),
+            ("cave".to_string(), Box::new(|map: &mut Map<char, char>, rng: &mut StdRng| {
+                CaveBuilder::new(map, &|_| '.', &|_| '#').create(rng, 0.45, 5, 4, 4).unwrap()
+            })),
+        ];
+        for algorithm in [MazeAlgorithm::BACKTRACKER, MazeAlgorithm::PRIM, MazeAlgorithm::KRUSKAL, MazeAlgorithm::ELLER] {
+            builders.push((format!("{:?}", algorithm), Box::new(move |map: &mut Map<char, char>, rng: &mut StdRng| {
+                MazeBuilder::new(map, &|_| '.', &|_| '#').create(rng, algorithm, 0.3).unwrap()
+            })));
+        }
+
+        for (name, create) in builders.iter() {
+            let map = build(61, 41, 1234, create.as_ref());
+            let again = build(61, 41, 1234, create.as_ref());
+
+            assert_eq!(tiles(&map), tiles(&again), "{}", name);
+            assert_eq!(rooms(&map), rooms(&again), "{}", name);
+            assert_ne!(tiles(&map), tiles(&build(61, 41, 4321, create.as_ref())), "{}", name);
+        }
     }
 }
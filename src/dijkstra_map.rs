@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use ndarray::{Array, Axis, Ix2};
+use crate::Map;
+
+/// Cost assigned to any location which cannot reach a goal.
+pub const UNREACHABLE: f32 = f32::INFINITY;
+
+/// Distance map flooded outward from any number of goals.  Each location holds the cheapest
+/// cost to reach the nearest goal using the same weight convention as `Map::shortest_path`
+/// (available returns 0 for impassable tiles and the cost to enter the tile otherwise).  A goal
+/// on a tile which cannot be entered is only reached from itself.
+///
+/// http://www.roguebasin.com/index.php/The_Incredible_Power_of_Dijkstra_Maps
+pub struct DijkstraMap {
+    data: Array<f32, Ix2>,
+}
+
+#[derive(PartialEq)]
+struct Frontier {
+    cost: f32,
+    loc: (usize, usize),
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    // Reversed so BinaryHeap pops the cheapest location first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then_with(|| self.loc.cmp(&other.loc))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl DijkstraMap {
+    pub fn new<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, goals: &[(usize, usize)],
+                                                     available: &dyn Fn(&T) -> usize) -> Self {
+        let seeds: Vec<((usize, usize), f32)> = goals.iter().map(|goal| (*goal, 0.)).collect();
+
+        Self::from_seeds(map, &seeds, available)
+    }
+
+    /// Flood from seeds which may already carry a cost.  Goals with a lower starting cost are
+    /// more attractive than others (e.g. a pile of gold vs a single coin).
+    pub fn from_seeds<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, seeds: &[((usize, usize), f32)],
+                                                            available: &dyn Fn(&T) -> usize) -> Self {
+        let mut dijkstra_map = Self {
+            data: Array::<f32, Ix2>::from_elem((map.width, map.height), UNREACHABLE),
+        };
+
+        dijkstra_map.flood(map, seeds, available);
+        dijkstra_map
+    }
+
+    #[inline]
+    pub fn get(&self, loc: (usize, usize)) -> Option<f32> {
+        self.data.get(loc).copied()
+    }
+
+    pub fn is_reachable(&self, loc: (usize, usize)) -> bool {
+        self.get(loc).is_some_and(|cost| cost != UNREACHABLE)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=((usize, usize), f32)> + '_ {
+        let width = self.data.len_of(Axis(0));
+        let height = self.data.len_of(Axis(1));
+
+        (0..width * height).map(move |index| {
+            let loc = (index % width, index / width);
+            (loc, self.data[loc])
+        })
+    }
+
+    /// Roll downhill: the adjacent available location with the lowest cost, provided it is
+    /// lower than where we are.  None means we are already at a goal (or a local minimum).
+    pub fn next_step<T: PartialEq, I: Default + PartialEq>(&self, map: &Map<T, I>, loc: &(usize, usize),
+                                                           available: &dyn Fn(&T) -> usize) -> Option<(usize, usize)> {
+        let mut best = (*loc, self.get(*loc)?);
+
+        for (next, _) in map.adjacent_ats(loc, available) {
+            if let Some(cost) = self.get(next) {
+                if cost < best.1 {
+                    best = (next, cost);
+                }
+            }
+        }
+
+        if best.0 == *loc {
+            None
+        } else {
+            Some(best.0)
+        }
+    }
+
+    /// Create a flee map.  Every reachable cost is multiplied by -coefficient and then the map
+    /// is rescanned so that rolling downhill leads away from the original goals rather than
+    /// just into the nearest corner.  A coefficient a bit larger than 1 (e.g. 1.2) makes fleeing
+    /// monsters willing to run past the threat to reach somewhere safer.
+    pub fn invert<T: PartialEq, I: Default + PartialEq>(&self, map: &Map<T, I>, coefficient: f32,
+                                                        available: &dyn Fn(&T) -> usize) -> Self {
+        let seeds: Vec<((usize, usize), f32)> = self.iter()
+            .filter(|(_, cost)| *cost != UNREACHABLE)
+            .map(|(loc, cost)| (loc, cost * -coefficient))
+            .collect();
+
+        Self::from_seeds(map, &seeds, available)
+    }
+
+    /// Weighted sum of several maps of the same dimensions.  A location unreachable in any
+    /// of the maps is unreachable in the result.  Returns None if no maps are given or their
+    /// dimensions differ.
+    pub fn combine(maps: &[(&DijkstraMap, f32)]) -> Option<Self> {
+        let (first, _) = maps.first()?;
+        let dim = first.data.dim();
+
+        if maps.iter().any(|(map, _)| map.data.dim() != dim) {
+            return None
+        }
+
+        let mut data = Array::<f32, Ix2>::zeros(dim);
+        for (map, weight) in maps {
+            for (total, cost) in data.iter_mut().zip(map.data.iter()) {
+                if *total == UNREACHABLE || *cost == UNREACHABLE {
+                    *total = UNREACHABLE;
+                } else {
+                    *total += cost * weight;
+                }
+            }
+        }
+
+        Some(Self { data })
+    }
+
+    fn flood<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, seeds: &[((usize, usize), f32)],
+                                                   available: &dyn Fn(&T) -> usize) {
+        let mut frontier = BinaryHeap::new();
+
+        for (loc, cost) in seeds {
+            if let Some(current) = self.data.get_mut(*loc) {
+                if *cost < *current {
+                    *current = *cost;
+                    frontier.push(Frontier { cost: *cost, loc: *loc });
+                }
+            }
+        }
+
+        while let Some(Frontier { cost, loc }) = frontier.pop() {
+            if cost > self.data[loc] {
+                continue
+            }
+
+            // Costs run towards the goals so what is paid is entering loc and not next.
+            let enter = available(&map.get(&loc).unwrap().solid);
+            if enter == 0 {
+                continue
+            }
+
+            for (next, _) in map.adjacent_ats(&loc, available) {
+                let next_cost = cost + enter as f32;
+                if next_cost < self.data[next] {
+                    self.data[next] = next_cost;
+                    frontier.push(Frontier { cost: next_cost, loc: next });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dijkstra_map::{DijkstraMap, UNREACHABLE};
+    use crate::map::generate_ascii_map;
+
+    const MAP: &str = "#######\n\
+                       #.....#\n\
+                       #.###.#\n\
+                       #.....#\n\
+                       #######";
+
+    fn available(tile: &char) -> usize {
+        if *tile == '.' { 1 } else { 0 }
+    }
+
+    #[test]
+    fn test_single_goal() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let dijkstra_map = DijkstraMap::new(&map, &[(1, 1)], &available);
+
+        assert_eq!(dijkstra_map.get((1, 1)), Some(0.));
+        assert_eq!(dijkstra_map.get((2, 1)), Some(1.));
+        assert_eq!(dijkstra_map.get((2, 2)), Some(UNREACHABLE));
+        assert_eq!(dijkstra_map.get((5, 3)), Some(5.));
+        assert!(!dijkstra_map.is_reachable((0, 0)));
+        assert_eq!(dijkstra_map.get((7, 0)), None);
+    }
+
+    #[test]
+    fn test_multiple_goals() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let dijkstra_map = DijkstraMap::new(&map, &[(1, 1), (5, 3)], &available);
+
+        assert_eq!(dijkstra_map.get((5, 1)), Some(2.));
+        assert_eq!(dijkstra_map.get((1, 3)), Some(2.));
+        assert_eq!(dijkstra_map.get((3, 3)), Some(2.));
+    }
+
+    #[test]
+    fn test_weighted_matches_shortest_path() {
+        let map = generate_ascii_map("map", "..~..\n").unwrap();
+        let weighted = |tile: &char| if *tile == '~' { 10 } else { 1 };
+        let dijkstra_map = DijkstraMap::new(&map, &[(2, 0)], &weighted);
+
+        assert_eq!(dijkstra_map.get((0, 0)), Some(11.));
+        for x in 0..5 {
+            let expected = map.shortest_path(&(x, 0), &(2, 0), &weighted).unwrap().1;
+            assert_eq!(dijkstra_map.get((x, 0)), Some(expected as f32));
+        }
+    }
+
+    #[test]
+    fn test_next_step() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let dijkstra_map = DijkstraMap::new(&map, &[(1, 1)], &available);
+
+        let mut loc = (5, 3);
+        let mut steps = 0;
+        while let Some(next) = dijkstra_map.next_step(&map, &loc, &available) {
+            loc = next;
+            steps += 1;
+        }
+
+        assert_eq!(loc, (1, 1));
+        assert_eq!(steps, 5);
+    }
+
+    #[test]
+    fn test_invert() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let dijkstra_map = DijkstraMap::new(&map, &[(1, 1)], &available);
+        let flee_map = dijkstra_map.invert(&map, 1.2, &available);
+
+        // Fleeing from next to the threat moves away from it.
+        let next = flee_map.next_step(&map, &(2, 1), &available).unwrap();
+        assert!(dijkstra_map.get(next).unwrap() > dijkstra_map.get((2, 1)).unwrap());
+        assert!(!flee_map.is_reachable((0, 0)));
+    }
+
+    #[test]
+    fn test_combine() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let a = DijkstraMap::new(&map, &[(1, 1)], &available);
+        let b = DijkstraMap::new(&map, &[(5, 3)], &available);
+        let combined = DijkstraMap::combine(&[(&a, 1.), (&b, 0.5)]).unwrap();
+
+        assert_eq!(combined.get((1, 1)), Some(2.5));
+        assert_eq!(combined.get((5, 3)), Some(5.));
+        assert_eq!(combined.get((0, 0)), Some(UNREACHABLE));
+        assert!(DijkstraMap::combine(&[]).is_none());
+    }
+}
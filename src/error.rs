@@ -0,0 +1,43 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A location which does not fit within a map of width x height.
+    OutOfBounds { loc: (usize, usize), width: usize, height: usize },
+    /// Text input which could not be parsed.  line and column are 1 based.
+    Malformed { line: usize, column: usize, message: String },
+    /// A size which is too small, too large or inconsistent.
+    InvalidDimensions(String),
+    /// Any other argument outside of the values it accepts.
+    InvalidArgument(String),
+    /// Random generation or search which could not produce a result.
+    GenerationFailed(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OutOfBounds { loc, width, height } =>
+                write!(f, "({}, {}) is outside of {}x{} map", loc.0, loc.1, width, height),
+            Error::Malformed { line, column, message } => write!(f, "{}:{}: {}", line, column, message),
+            Error::InvalidDimensions(message) => write!(f, "invalid dimensions: {}", message),
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            Error::GenerationFailed(message) => write!(f, "generation failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Error::OutOfBounds { loc: (5, 1), width: 3, height: 4 }.to_string(), "(5, 1) is outside of 3x4 map");
+        assert_eq!(Error::Malformed { line: 2, column: 7, message: "bad".to_string() }.to_string(), "2:7: bad");
+        assert_eq!(Error::InvalidDimensions("too small".to_string()).to_string(), "invalid dimensions: too small");
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_4, FRAC_PI_8, PI, TAU};
+use crate::{Map, Overlay, Spot};
+use crate::field_of_view::{shadow_cast, MULTIPLIERS};
+
+// Slack so locations exactly on the edge of the cone are not lost to rounding.
+const EPSILON: f32 = 1e-4;
+
+// Index into MULTIPLIERS for each octant in the order the octant mask uses them.
+const OCTANTS: [usize; 8] = [5, 4, 7, 6, 1, 0, 3, 2];
+
+/// Field of view limited to the octants set in octants.  Angles are measured the same way as
+/// calculate_cone_field_of_view so bit 0 is east to south east, bit 1 south east to south and so
+/// on clockwise around to bit 7 which is north east to east.  Only those octants are scanned.
+pub fn calculate_octant_field_of_view<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, start: &(usize, usize),
+                                                                           radius: usize, octants: u8,
+                                                                           light_map: &mut Overlay<bool>,
+                                                                           visible: &dyn Fn(&Spot<T, I>) -> bool) {
+    light_map.reset();
+    light_map.set(*start, true);
+
+    for (octant, multipliers) in OCTANTS.iter().enumerate() {
+        if octants & (1 << octant) != 0 {
+            shadow_cast(1, 1.0, 0.0, MULTIPLIERS[*multipliers], radius, start, light_map, map, visible);
+        }
+    }
+}
+
+/// Field of view limited to a cone for things which only see in front of them.  facing is in
+/// radians using map coordinates so 0 is east (+x) and PI / 2 is south (+y).  A location is in
+/// the cone when the direction to its center is within half_angle of facing.  Walls still block
+/// sight exactly like calculate_field_of_view.
+pub fn calculate_cone_field_of_view<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, start: &(usize, usize),
+                                                                         radius: usize, facing: f32, half_angle: f32,
+                                                                         light_map: &mut Overlay<bool>,
+                                                                         visible: &dyn Fn(&Spot<T, I>) -> bool) {
+    calculate_octant_field_of_view(map, start, radius, cone_octants(facing, half_angle), light_map, visible);
+
+    if half_angle >= PI {
+        return
+    }
+
+    // On a wrapping map a large radius can reach the same location from both sides so it is
+    // only outside the cone when every way of reaching it is.
+    let mut inside = HashSet::new();
+    let mut outside = vec![];
+    let radius = radius as isize;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let Some(loc) = map.wrap_loc(start.0 as isize + dx, start.1 as isize + dy) else {
+                continue
+            };
+
+            if loc != *start && *light_map.get(loc).unwrap() {
+                let angle = (dy as f32).atan2(dx as f32);
+
+                if angle_between(angle, facing) > half_angle + EPSILON {
+                    outside.push(loc);
+                } else {
+                    inside.insert(loc);
+                }
+            }
+        }
+    }
+
+    for loc in outside.into_iter().filter(|loc| !inside.contains(loc)) {
+        light_map.set(loc, false);
+    }
+}
+
+/// Octant mask (see calculate_octant_field_of_view) of every octant which overlaps the cone.
+pub fn cone_octants(facing: f32, half_angle: f32) -> u8 {
+    (0..8).filter(|octant| {
+        let middle = *octant as f32 * FRAC_PI_4 + FRAC_PI_8;
+
+        let difference = angle_between(middle, facing);
+
+        // Octants which only touch the edge of the cone are skipped unless the cone is a single ray.
+        difference + EPSILON < half_angle + FRAC_PI_8 || difference <= FRAC_PI_8 + EPSILON
+    }).fold(0, |mask, octant| mask | (1 << octant))
+}
+
+// Smallest absolute difference between two angles.
+fn angle_between(a: f32, b: f32) -> f32 {
+    ((a - b + PI).rem_euclid(TAU) - PI).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use crate::field_of_view::{calculate_cone_field_of_view, calculate_field_of_view, calculate_octant_field_of_view,
+                               cone_octants};
+    use crate::map::generate_ascii_map;
+    use crate::{Map, Spot, Wrap};
+
+    fn visible(spot: &Spot<char, char>) -> bool {
+        spot.solid == '.'
+    }
+
+    #[test]
+    fn test_cone_octants() {
+        assert_eq!(cone_octants(0.0, FRAC_PI_4), 0b1000_0001);
+        assert_eq!(cone_octants(FRAC_PI_2, 0.1), 0b0000_0110);
+        assert_eq!(cone_octants(-FRAC_PI_2, FRAC_PI_4), 0b0110_0000);
+        assert_eq!(cone_octants(1.0, PI), 0b1111_1111);
+    }
+
+    #[test]
+    fn test_octants() {
+        let map: Map<char, char> = Map::new("map", 7, 7, &|_| '.');
+        let mut light_map = map.create_overlay();
+        calculate_octant_field_of_view(&map, &(3, 3), 4, 0b0000_0011, &mut light_map, &visible);
+
+        assert_eq!(format!("{}", light_map), "#######\n\
+                                               #######\n\
+                                               #######\n\
+                                               ###....\n\
+                                               ###....\n\
+                                               ###....\n\
+                                               ###...#\n");
+    }
+
+    #[test]
+    fn test_cone_facing_east() {
+        let map: Map<char, char> = Map::new("map", 9, 9, &|_| '.');
+        let mut light_map = map.create_overlay();
+        calculate_cone_field_of_view(&map, &(4, 4), 5, 0.0, FRAC_PI_4, &mut light_map, &visible);
+
+        assert_eq!(format!("{}", light_map), "#########\n\
+                                               #######.#\n\
+                                               ######...\n\
+                                               #####....\n\
+                                               ####.....\n\
+                                               #####....\n\
+                                               ######...\n\
+                                               #######.#\n\
+                                               #########\n");
+    }
+
+    #[test]
+    fn test_cone_occluded() {
+        let map = generate_ascii_map("map", ".......\n\
+                                              ...#...\n\
+                                              .......\n\
+                                              .......\n").unwrap();
+        let mut light_map = map.create_overlay();
+        calculate_cone_field_of_view(&map, &(3, 3), 5, -FRAC_PI_2, 0.5, &mut light_map, &visible);
+
+        assert!(*light_map.get((3, 2)).unwrap());
+        assert!(*light_map.get((3, 1)).unwrap());
+        assert!(!*light_map.get((3, 0)).unwrap());
+        assert!(*light_map.get((3, 3)).unwrap());
+        assert!(!*light_map.get((0, 3)).unwrap());
+    }
+
+    #[test]
+    fn test_full_circle_matches_fov() {
+        let map = generate_ascii_map("map", "..#....\n\
+                                              .......\n\
+                                              ....#..\n\
+                                              .......\n").unwrap();
+        let mut cone = map.create_overlay();
+        let mut full = map.create_overlay();
+        calculate_cone_field_of_view(&map, &(1, 2), 6, 2.0, PI, &mut cone, &visible);
+        calculate_field_of_view(&map, &(1, 2), 6, &mut full, &visible);
+
+        assert_eq!(format!("{}", cone), format!("{}", full));
+    }
+
+    #[test]
+    fn test_cone_wider_than_wrapped_map() {
+        let map: Map<char, char> = Map::new("map", 7, 3, &|_| '.').with_wrap(Wrap::HORIZONTAL);
+        let mut light_map = map.create_overlay();
+        calculate_cone_field_of_view(&map, &(3, 1), 5, 0.0, FRAC_PI_4, &mut light_map, &visible);
+
+        // (0, 1) is 4 east (in the cone) and also 3 west (behind) but (2, 1) is only ever behind.
+        assert_eq!(format!("{}", light_map), ".###...\n\
+                                               .##....\n\
+                                               .###...\n");
+    }
+}
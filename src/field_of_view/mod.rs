@@ -0,0 +1,245 @@
+use crate::{Map, Overlay, Spot};
+use crate::field_of_view::permissive::precise_permissive;
+use crate::field_of_view::symmetric::symmetric_shadow_cast;
+
+mod cone;
+mod permissive;
+mod symmetric;
+
+pub use cone::{calculate_cone_field_of_view, calculate_octant_field_of_view, cone_octants};
+
+const MULTIPLIERS: [(isize, isize, isize, isize); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// Common interface for field of view algorithms so callers can swap between them.  Every
+/// implementation resets light_map and then sets each location visible from start within
+/// radius to true.  visible says whether light passes through a spot.
+pub trait FieldOfView<T: PartialEq, I: Default + PartialEq> {
+    fn calculate(&self, map: &Map<T, I>, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                 visible: &dyn Fn(&Spot<T, I>) -> bool);
+}
+
+/// RogueBasin recursive shadowcasting.  Fast but a monster may see you when you cannot see it.
+pub struct RecursiveShadowcasting;
+
+/// Albert Ford's symmetric shadowcasting using exact slopes.  One floor tile sees another if
+/// and only if the other sees it.
+pub struct SymmetricShadowcasting;
+
+/// Precise permissive.  A tile is visible if any line from anywhere in the start tile reaches
+/// anywhere in it.  The most generous of the algorithms.
+pub struct PrecisePermissive;
+
+/// Walls block light as diamonds inscribed in their tile so light slips past corners and
+/// around pillars.  Any tile the light touches is visible.
+pub struct DiamondWalls;
+
+impl<T: PartialEq, I: Default + PartialEq> FieldOfView<T, I> for RecursiveShadowcasting {
+    fn calculate(&self, map: &Map<T, I>, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                 visible: &dyn Fn(&Spot<T, I>) -> bool) {
+        calculate_field_of_view(map, start, radius, light_map, visible);
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> FieldOfView<T, I> for SymmetricShadowcasting {
+    fn calculate(&self, map: &Map<T, I>, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                 visible: &dyn Fn(&Spot<T, I>) -> bool) {
+        light_map.reset();
+        light_map.set(*start, true);
+        symmetric_shadow_cast(map, start, radius, light_map, visible, true);
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> FieldOfView<T, I> for PrecisePermissive {
+    fn calculate(&self, map: &Map<T, I>, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                 visible: &dyn Fn(&Spot<T, I>) -> bool) {
+        light_map.reset();
+        light_map.set(*start, true);
+        precise_permissive(map, start, radius, light_map, visible);
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> FieldOfView<T, I> for DiamondWalls {
+    fn calculate(&self, map: &Map<T, I>, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                 visible: &dyn Fn(&Spot<T, I>) -> bool) {
+        light_map.reset();
+        light_map.set(*start, true);
+        symmetric_shadow_cast(map, start, radius, light_map, visible, false);
+    }
+}
+
+/// Every built in algorithm so one can be picked from configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FovAlgorithm {
+    SHADOWCASTING,
+    SYMMETRIC,
+    PERMISSIVE,
+    DIAMOND,
+}
+
+impl<T: PartialEq, I: Default + PartialEq> FieldOfView<T, I> for FovAlgorithm {
+    fn calculate(&self, map: &Map<T, I>, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                 visible: &dyn Fn(&Spot<T, I>) -> bool) {
+        let algorithm: &dyn FieldOfView<T, I> = match self {
+            FovAlgorithm::SHADOWCASTING => &RecursiveShadowcasting,
+            FovAlgorithm::SYMMETRIC => &SymmetricShadowcasting,
+            FovAlgorithm::PERMISSIVE => &PrecisePermissive,
+            FovAlgorithm::DIAMOND => &DiamondWalls,
+        };
+
+        algorithm.calculate(map, start, radius, light_map, visible);
+    }
+}
+
+/// Calculate field of view from start using the chosen algorithm.
+pub fn calculate_field_of_view_using<T: PartialEq, I: Default + PartialEq>(algorithm: FovAlgorithm, map: &Map<T, I>,
+                                                                          start: &(usize, usize), radius: usize,
+                                                                          light_map: &mut Overlay<bool>,
+                                                                          visible: &dyn Fn(&Spot<T, I>) -> bool) {
+    algorithm.calculate(map, start, radius, light_map, visible);
+}
+
+// http://www.roguebasin.com/index.php/FOV_using_recursive_shadowcasting
+pub fn calculate_field_of_view<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, start: &(usize, usize), radius: usize,
+                               light_map: &mut Overlay<bool>, visible: &dyn Fn(&Spot<T, I>) -> bool) {
+    light_map.reset();
+    light_map.set(*start, true);
+
+    for multipliers in MULTIPLIERS {
+        shadow_cast(1, 1.0, 0.0, multipliers, radius, start, light_map, map, visible);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn shadow_cast<T: PartialEq, I: Default + PartialEq>(row: usize, mut begin: f32, end: f32, mults: (isize, isize, isize, isize),
+               radius: usize, start: &(usize, usize), light_map: &mut Overlay<bool>, map: &Map<T, I>,
+               visible: &dyn Fn(&Spot<T, I>) -> bool) {
+    if begin < end {
+        return
+    }
+
+    let radius_2: isize = radius as isize * radius as isize;
+    let mut new_begin = 0.;
+    let mut blocked = false;
+    for y in row..radius {
+        let mut dx = -(y as isize) - 1;
+        let dy = -(y as isize);
+        while dx <= 0 {
+            dx += 1;
+            let current_x = start.0 as isize + dx * mults.0 + dy * mults.1;
+            let current_y = start.1 as isize + dx * mults.2 + dy * mults.3;
+
+            // Make sure we are still on the map (or wrap around to the other side of it).
+            let Some(current) = map.wrap_loc(current_x, current_y) else {
+                continue
+            };
+
+            // Slope at right edge of current square.
+            let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+            if begin < right_slope {
+                continue
+            }
+
+            let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+            if end > left_slope {
+                break
+            }
+
+            if dx * dx + dy * dy < radius_2 {
+                light_map.set(current, true);
+            }
+
+            if blocked {
+                if !(visible)(map.get(&current).unwrap()) {
+                    // Already blocked for the last 'column'.  More of the same continue on until
+                    // we find an open spot.  Keep track of slope to use it when we unblock (nothing
+                    // to the left can be seen from this point on next rows).
+                    new_begin = right_slope;
+                } else {
+                    // We see an open position.  Unblock.  Use previous saved scope as safe slope
+                    // we know we cannot see anything to the left of.
+                    blocked = false;
+                    begin = new_begin;
+                }
+            } else {
+                if !(visible)(map.get(&current).unwrap()) && y < radius {
+                    // Ran into our first blocked item.  Scan next row but only up to new slope since
+                    // we know we can see nothing more to the right of it.
+                    blocked = true;
+                    shadow_cast(y + 1, begin, left_slope, mults, radius, start, light_map, map, visible);
+                    new_begin = right_slope;
+                }
+            }
+        }
+        if blocked {
+            break
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field_of_view::{calculate_cone_field_of_view, calculate_field_of_view, FieldOfView, FovAlgorithm};
+    use crate::map::generate_ascii_map;
+    use crate::{Spot, Wrap};
+
+    const FOV_MAP: &str = ".................\n\
+                           .......###.......\n\
+                           .......###.......\n\
+                           .......###..#..##\n\
+                           .......##........\n\
+                           .................\n\
+                           .................\n";
+
+    const FOV_ANSWER_7_6: &str = "......######....#\n\
+                                  .......####....##\n\
+                                  .......###....#.#\n\
+                                  .......##........\n\
+                                  .................\n\
+                                  .................\n\
+                                  .................\n";
+
+    #[test]
+    fn test_fov() {
+        let map = generate_ascii_map("map", FOV_MAP).unwrap();
+        let mut light_map = map.create_overlay();
+        let visible = |place: &Spot<char, char>| place.solid == '.';
+        calculate_field_of_view(&map, &(7, 6), 20, &mut light_map, &visible);
+
+        let ascii = format!("{}", &light_map);
+
+        println!("{}", ascii);
+        assert_eq!(ascii, FOV_ANSWER_7_6)
+    }
+
+    #[test]
+    fn test_fov_wrap() {
+        let map = generate_ascii_map("map", "..#....\n\
+                                             .......\n\
+                                             .......\n").unwrap().with_wrap(Wrap::HORIZONTAL);
+        let mut light_map = map.create_overlay();
+        let visible = |place: &Spot<char, char>| place.solid == '.';
+
+        for algorithm in [FovAlgorithm::SHADOWCASTING, FovAlgorithm::SYMMETRIC, FovAlgorithm::PERMISSIVE, FovAlgorithm::DIAMOND] {
+            algorithm.calculate(&map, &(0, 1), 3, &mut light_map, &visible);
+
+            // Across the left edge and around to the wall on the right.
+            assert!(*light_map.get((6, 1)).unwrap(), "{:?}", algorithm);
+            assert!(*light_map.get((5, 1)).unwrap(), "{:?}", algorithm);
+            assert!(!*light_map.get((3, 1)).unwrap(), "{:?}", algorithm);
+        }
+
+        // Looking west only lights the far side of the map.
+        calculate_cone_field_of_view(&map, &(0, 1), 3, std::f32::consts::PI, 0.5, &mut light_map, &visible);
+        assert!(*light_map.get((5, 1)).unwrap());
+        assert!(!*light_map.get((1, 1)).unwrap());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::{Map, Overlay, Spot};
+
+// Quadrant directions: north east, south east, south west and north west.
+const QUADRANTS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+#[derive(Clone, Copy)]
+struct Line {
+    xi: isize,
+    yi: isize,
+    xf: isize,
+    yf: isize,
+}
+
+impl Line {
+    #[inline]
+    fn relative_slope(&self, x: isize, y: isize) -> isize {
+        (self.yf - self.yi) * (self.xf - x) - (self.xf - self.xi) * (self.yf - y)
+    }
+
+    fn is_below(&self, x: isize, y: isize) -> bool {
+        self.relative_slope(x, y) > 0
+    }
+
+    fn is_below_or_collinear(&self, x: isize, y: isize) -> bool {
+        self.relative_slope(x, y) >= 0
+    }
+
+    fn is_above(&self, x: isize, y: isize) -> bool {
+        self.relative_slope(x, y) < 0
+    }
+
+    fn is_above_or_collinear(&self, x: isize, y: isize) -> bool {
+        self.relative_slope(x, y) <= 0
+    }
+
+    fn is_collinear(&self, x: isize, y: isize) -> bool {
+        self.relative_slope(x, y) == 0
+    }
+
+    fn is_line_collinear(&self, other: &Line) -> bool {
+        self.is_collinear(other.xi, other.yi) && self.is_collinear(other.xf, other.yf)
+    }
+}
+
+// Bumps form linked lists back to their parent bump.  They never change once made so views
+// which are copied can safely share them.
+struct Bump {
+    x: isize,
+    y: isize,
+    parent: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+struct View {
+    shallow_line: Line,
+    steep_line: Line,
+    shallow_bump: Option<usize>,
+    steep_bump: Option<usize>,
+}
+
+struct Quadrant<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a Map<T, I>,
+    start: (usize, usize),
+    direction: (isize, isize),
+    radius: isize,
+    light_map: &'a mut Overlay<bool>,
+    visible: &'a dyn Fn(&Spot<T, I>) -> bool,
+    views: Vec<View>,
+    bumps: Vec<Bump>,
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> Quadrant<'a, T, I> {
+    fn check(&mut self, extent_x: isize, extent_y: isize) {
+        self.views.push(View {
+            shallow_line: Line { xi: 0, yi: 1, xf: extent_x, yf: 0 },
+            steep_line: Line { xi: 1, yi: 0, xf: 0, yf: extent_y },
+            shallow_bump: None,
+            steep_bump: None,
+        });
+
+        // Walk diagonals out from the start so nearer tiles are always visited first.
+        for i in 1..=extent_x + extent_y {
+            if self.views.is_empty() {
+                break
+            }
+
+            let mut index = 0;
+            for j in (i - extent_x).max(0)..=i.min(extent_y) {
+                if index >= self.views.len() {
+                    break
+                }
+                index = self.visit(i - j, j, index);
+            }
+        }
+    }
+
+    // Returns the view index to continue with.
+    fn visit(&mut self, x: isize, y: isize, mut index: usize) -> usize {
+        let top_left = (x, y + 1);
+        let bottom_right = (x + 1, y);
+
+        while index < self.views.len() && self.views[index].steep_line.is_below_or_collinear(bottom_right.0, bottom_right.1) {
+            index += 1;
+        }
+
+        if index == self.views.len() || self.views[index].shallow_line.is_above_or_collinear(top_left.0, top_left.1) {
+            return index
+        }
+
+        let loc = self.map.wrap_loc(self.start.0 as isize + x * self.direction.0,
+                                    self.start.1 as isize + y * self.direction.1).unwrap();
+        if x * x + y * y < self.radius * self.radius {
+            self.light_map.set(loc, true);
+        }
+
+        if (self.visible)(self.map.get(&loc).unwrap()) {
+            return index
+        }
+
+        let view = self.views[index];
+        let above = view.shallow_line.is_above(bottom_right.0, bottom_right.1);
+        let below = view.steep_line.is_below(top_left.0, top_left.1);
+
+        if above && below {
+            // Blocker fills the entire view.
+            self.views.remove(index);
+        } else if above {
+            self.add_shallow_bump(top_left, index);
+            self.check_view(index);
+        } else if below {
+            self.add_steep_bump(bottom_right, index);
+            self.check_view(index);
+        } else {
+            // Blocker is in the middle of the view so split it in two.
+            let shallow_index = index;
+            let mut steep_index = index + 1;
+            index += 1;
+            self.views.insert(shallow_index, view);
+            self.add_steep_bump(bottom_right, shallow_index);
+            if !self.check_view(shallow_index) {
+                index -= 1;
+                steep_index -= 1;
+            }
+            self.add_shallow_bump(top_left, steep_index);
+            self.check_view(steep_index);
+        }
+
+        index
+    }
+
+    fn add_shallow_bump(&mut self, (x, y): (isize, isize), index: usize) {
+        let view = &mut self.views[index];
+        view.shallow_line.xf = x;
+        view.shallow_line.yf = y;
+        self.bumps.push(Bump { x, y, parent: view.shallow_bump });
+        view.shallow_bump = Some(self.bumps.len() - 1);
+
+        let mut current = view.steep_bump;
+        while let Some(bump) = current {
+            let bump = &self.bumps[bump];
+            if view.shallow_line.is_above(bump.x, bump.y) {
+                view.shallow_line.xi = bump.x;
+                view.shallow_line.yi = bump.y;
+            }
+            current = bump.parent;
+        }
+    }
+
+    fn add_steep_bump(&mut self, (x, y): (isize, isize), index: usize) {
+        let view = &mut self.views[index];
+        view.steep_line.xf = x;
+        view.steep_line.yf = y;
+        self.bumps.push(Bump { x, y, parent: view.steep_bump });
+        view.steep_bump = Some(self.bumps.len() - 1);
+
+        let mut current = view.shallow_bump;
+        while let Some(bump) = current {
+            let bump = &self.bumps[bump];
+            if view.steep_line.is_below(bump.x, bump.y) {
+                view.steep_line.xi = bump.x;
+                view.steep_line.yi = bump.y;
+            }
+            current = bump.parent;
+        }
+    }
+
+    // Removes the view if it has narrowed to nothing.  Returns false if it was removed.
+    fn check_view(&mut self, index: usize) -> bool {
+        let View { shallow_line, steep_line, .. } = self.views[index];
+
+        if shallow_line.is_line_collinear(&steep_line) && (shallow_line.is_collinear(0, 1) || shallow_line.is_collinear(1, 0)) {
+            self.views.remove(index);
+            false
+        } else {
+            true
+        }
+    }
+}
+
+// http://www.roguebasin.com/index.php/Precise_Permissive_Field_of_View
+pub(crate) fn precise_permissive<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, start: &(usize, usize), radius: usize,
+                                                                      light_map: &mut Overlay<bool>,
+                                                                      visible: &dyn Fn(&Spot<T, I>) -> bool) {
+    if !map.is_valid_loc(start) {
+        return
+    }
+
+    let radius = radius as isize;
+    let (x, y) = (start.0 as isize, start.1 as isize);
+    let (width, height) = (map.width as isize, map.height as isize);
+
+    for direction in QUADRANTS {
+        // Wrapping edges never stop the view.
+        let extent_x = if map.wrap.is_horizontal() { radius } else if direction.0 > 0 { width - x - 1 } else { x }.min(radius);
+        let extent_y = if map.wrap.is_vertical() { radius } else if direction.1 > 0 { height - y - 1 } else { y }.min(radius);
+
+        let mut quadrant = Quadrant {
+            map,
+            start: *start,
+            direction,
+            radius,
+            light_map,
+            visible,
+            views: vec![],
+            bumps: vec![],
+        };
+        quadrant.check(extent_x, extent_y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::field_of_view::{DiamondWalls, FieldOfView, FovAlgorithm, PrecisePermissive, RecursiveShadowcasting,
+                               SymmetricShadowcasting};
+    use crate::map::generate_ascii_map;
+    use crate::{Map, Overlay, Spot};
+
+    fn visible(spot: &Spot<char, char>) -> bool {
+        spot.solid == '.'
+    }
+
+    #[test]
+    fn test_open_map() {
+        let map: Map<char, char> = Map::new("map", 9, 9, &|_| '.');
+        let mut light_map = map.create_overlay();
+        PrecisePermissive.calculate(&map, &(4, 4), 4, &mut light_map, &visible);
+
+        assert_eq!(format!("{}", light_map), "#########\n\
+                                               ##.....##\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               ##.....##\n\
+                                               #########\n");
+    }
+
+    #[test]
+    fn test_wall_blocks() {
+        let map = generate_ascii_map("map", ".....\n\
+                                              ..#..\n\
+                                              .....\n").unwrap();
+        let mut light_map = map.create_overlay();
+        PrecisePermissive.calculate(&map, &(2, 2), 5, &mut light_map, &visible);
+
+        assert!(*light_map.get((2, 1)).unwrap());
+        assert!(!*light_map.get((2, 0)).unwrap());
+        assert!(*light_map.get((0, 0)).unwrap());
+    }
+
+    #[test]
+    fn test_sees_past_corner() {
+        // Looking diagonally between two walls only touching at a corner.
+        let map = generate_ascii_map("map", "...\n\
+                                              .#.\n\
+                                              #..\n").unwrap();
+        let mut light_map = map.create_overlay();
+        PrecisePermissive.calculate(&map, &(0, 1), 5, &mut light_map, &visible);
+
+        assert!(*light_map.get((1, 2)).unwrap());
+        assert!(*light_map.get((2, 0)).unwrap());
+    }
+
+    #[test]
+    fn test_more_permissive_than_symmetric() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut map: Map<char, char> = Map::new("map", 16, 16, &|_| '.');
+        for _ in 0..40 {
+            map.set(&(rng.gen_range(0..16), rng.gen_range(0..16)), Spot::new('#', None));
+        }
+        map.set(&(8, 8), Spot::new('.', None));
+
+        let mut symmetric = map.create_overlay();
+        let mut diamond = map.create_overlay();
+        let mut permissive = map.create_overlay();
+        SymmetricShadowcasting.calculate(&map, &(8, 8), 10, &mut symmetric, &visible);
+        DiamondWalls.calculate(&map, &(8, 8), 10, &mut diamond, &visible);
+        PrecisePermissive.calculate(&map, &(8, 8), 10, &mut permissive, &visible);
+
+        // Not a strict superset since walls are shaped differently but far more is seen overall.
+        let count = |overlay: &Overlay<bool>| map.iter().filter(|(loc, _)| *overlay.get(*loc).unwrap()).count();
+        assert!(count(&diamond) > count(&symmetric));
+        assert!(count(&permissive) > count(&symmetric));
+    }
+
+    #[test]
+    fn test_swappable() {
+        let map: Map<char, char> = Map::new("map", 9, 9, &|_| '.');
+        let algorithms: Vec<Box<dyn FieldOfView<char, char>>> = vec![
+            Box::new(RecursiveShadowcasting), Box::new(SymmetricShadowcasting), Box::new(PrecisePermissive),
+            Box::new(DiamondWalls), Box::new(FovAlgorithm::PERMISSIVE),
+        ];
+
+        for algorithm in algorithms {
+            let mut light_map = map.create_overlay();
+            algorithm.calculate(&map, &(4, 4), 3, &mut light_map, &visible);
+
+            assert!(*light_map.get((4, 4)).unwrap());
+            assert!(*light_map.get((5, 5)).unwrap());
+            assert!(!*light_map.get((0, 0)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_start_off_map() {
+        let map: Map<char, char> = Map::new("map", 5, 5, &|_| '.');
+        let algorithms: Vec<Box<dyn FieldOfView<char, char>>> = vec![
+            Box::new(RecursiveShadowcasting), Box::new(SymmetricShadowcasting), Box::new(PrecisePermissive),
+            Box::new(DiamondWalls),
+        ];
+
+        for algorithm in algorithms {
+            let mut light_map = map.create_overlay();
+            algorithm.calculate(&map, &(8, 2), 4, &mut light_map, &visible);
+
+            assert_eq!(light_map.count(&|lit| *lit), 0);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{Map, Overlay, Spot};
+
+// (col, row) -> (dx, dy) as (dx per col, dx per row, dy per col, dy per row) for north, east,
+// south and west.
+const QUADRANTS: [(isize, isize, isize, isize); 4] = [
+    (1, 0, 0, -1),
+    (0, 1, 1, 0),
+    (1, 0, 0, 1),
+    (0, -1, 1, 0),
+];
+
+/// Exact rational slope so there is no f32 rounding to break symmetry.  den is always positive.
+#[derive(Clone, Copy)]
+struct Slope {
+    num: isize,
+    den: isize,
+}
+
+impl Slope {
+    // Slope to the left edge of a tile.
+    fn edge(depth: isize, col: isize) -> Self {
+        Self { num: 2 * col - 1, den: 2 * depth }
+    }
+
+    // depth * slope rounded to nearest column with ties rounding up.
+    fn round_up(&self, depth: isize) -> isize {
+        (2 * depth * self.num + self.den).div_euclid(2 * self.den)
+    }
+
+    // depth * slope rounded to nearest column with ties rounding down.
+    fn round_down(&self, depth: isize) -> isize {
+        -(self.den - 2 * depth * self.num).div_euclid(2 * self.den)
+    }
+}
+
+struct Scanner<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a Map<T, I>,
+    start: (usize, usize),
+    radius: isize,
+    light_map: &'a mut Overlay<bool>,
+    visible: &'a dyn Fn(&Spot<T, I>) -> bool,
+    quadrant: (isize, isize, isize, isize),
+    // When false any floor the beam touches is seen, not just those whose center is inside it.
+    symmetric: bool,
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> Scanner<'a, T, I> {
+    fn loc(&self, depth: isize, col: isize) -> Option<(usize, usize)> {
+        let (xc, xr, yc, yr) = self.quadrant;
+        let x = self.start.0 as isize + col * xc + depth * xr;
+        let y = self.start.1 as isize + col * yc + depth * yr;
+
+        self.map.wrap_loc(x, y)
+    }
+
+    // Anything off the map blocks sight just like a wall.
+    fn is_wall(&self, loc: Option<(usize, usize)>) -> bool {
+        loc.is_none_or(|loc| !(self.visible)(self.map.get(&loc).unwrap()))
+    }
+
+    fn scan(&mut self, depth: isize, mut begin: Slope, end: Slope) {
+        if depth >= self.radius {
+            return
+        }
+
+        let mut previous_wall = None;
+        for col in begin.round_up(depth)..=end.round_down(depth) {
+            let loc = self.loc(depth, col);
+            let wall = self.is_wall(loc);
+
+            // Floors are only seen if their center is within the beam which is what makes
+            // this symmetric.  Walls are seen if any part of them is.
+            let centered = col * begin.den >= depth * begin.num && col * end.den <= depth * end.num;
+            if let Some(loc) = loc {
+                if (wall || centered || !self.symmetric) && col * col + depth * depth < self.radius * self.radius {
+                    self.light_map.set(loc, true);
+                }
+            }
+
+            if previous_wall == Some(true) && !wall {
+                begin = Slope::edge(depth, col);
+            }
+
+            if previous_wall == Some(false) && wall {
+                self.scan(depth + 1, begin, Slope::edge(depth, col));
+            }
+
+            previous_wall = Some(wall);
+        }
+
+        if previous_wall == Some(false) {
+            self.scan(depth + 1, begin, end);
+        }
+    }
+}
+
+// https://www.albertford.com/shadowcasting/
+//
+// Walls only cast a shadow as wide as their center row which is the same as treating them as
+// diamonds inscribed in their tile.  With symmetric false this is diamond walls FOV.
+pub(crate) fn symmetric_shadow_cast<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, start: &(usize, usize), radius: usize,
+                                                                         light_map: &mut Overlay<bool>,
+                                                                         visible: &dyn Fn(&Spot<T, I>) -> bool,
+                                                                         symmetric: bool) {
+    for quadrant in QUADRANTS {
+        let mut scanner = Scanner {
+            map,
+            start: *start,
+            radius: radius as isize,
+            light_map,
+            visible,
+            quadrant,
+            symmetric,
+        };
+
+        scanner.scan(1, Slope { num: -1, den: 1 }, Slope { num: 1, den: 1 });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::field_of_view::{calculate_field_of_view_using, FovAlgorithm};
+    use crate::map::generate_ascii_map;
+    use crate::{Map, Overlay, Spot};
+
+    const SIZE: usize = 16;
+
+    fn random_map(seed: u64) -> Map<char, char> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut map = Map::new("map", SIZE, SIZE, &|_| '.');
+
+        for _ in 0..40 {
+            let loc = (rng.gen_range(0..SIZE), rng.gen_range(0..SIZE));
+            map.set(&loc, Spot::new('#', None));
+        }
+
+        map
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let visible = |spot: &Spot<char, char>| spot.solid == '.';
+
+        for seed in 0..4 {
+            let map = random_map(seed);
+            let floors: Vec<(usize, usize)> = map.iter()
+                .filter(|(_, spot)| spot.solid == '.')
+                .map(|(loc, _)| loc)
+                .collect();
+            let fovs: Vec<Overlay<bool>> = floors.iter().map(|loc| {
+                let mut light_map = map.create_overlay();
+                calculate_field_of_view_using(FovAlgorithm::SYMMETRIC, &map, loc, 10, &mut light_map, &visible);
+                light_map
+            }).collect();
+
+            for (a, fov_a) in floors.iter().zip(fovs.iter()) {
+                for (b, fov_b) in floors.iter().zip(fovs.iter()) {
+                    assert_eq!(fov_a.get(*b), fov_b.get(*a), "seed {} from {:?} to {:?}", seed, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_map() {
+        let map: Map<char, char> = Map::new("map", 9, 9, &|_| '.');
+        let mut light_map = map.create_overlay();
+        calculate_field_of_view_using(FovAlgorithm::SYMMETRIC, &map, &(4, 4), 4, &mut light_map,
+                                      &|spot| spot.solid == '.');
+
+        assert_eq!(format!("{}", light_map), "#########\n\
+                                               ##.....##\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               #.......#\n\
+                                               ##.....##\n\
+                                               #########\n");
+    }
+
+    #[test]
+    fn test_wall_blocks() {
+        let map = generate_ascii_map("map", ".....\n\
+                                              ..#..\n\
+                                              .....\n").unwrap();
+        let mut light_map = map.create_overlay();
+        calculate_field_of_view_using(FovAlgorithm::SYMMETRIC, &map, &(2, 2), 5, &mut light_map,
+                                      &|spot| spot.solid == '.');
+
+        assert!(*light_map.get((2, 1)).unwrap());
+        assert!(!*light_map.get((2, 0)).unwrap());
+        assert!(*light_map.get((0, 0)).unwrap());
+    }
+}
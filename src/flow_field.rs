@@ -0,0 +1,286 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use ndarray::{Array, Ix2};
+use crate::{Map, Movement, Wrap};
+
+const UNREACHED: usize = usize::MAX;
+
+type Frontier = BinaryHeap<Reverse<(usize, (usize, usize))>>;
+
+/// Direction to move from every location to reach the nearest of any number of targets.  Built
+/// from an integration field (the cheapest cost from each location to a target) so any number
+/// of units can share one field instead of each searching for its own path.  When tiles change
+/// update only recomputes the locations whose route went through them.
+pub struct FlowField {
+    movement: Movement,
+    wrap: Wrap,
+    targets: Vec<(usize, usize)>,
+    costs: Array<usize, Ix2>,
+    directions: Array<Option<(isize, isize)>, Ix2>,
+}
+
+impl FlowField {
+    pub fn new<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, movement: &Movement, targets: &[(usize, usize)],
+                                                     available: &dyn Fn(&T) -> usize) -> Self {
+        let mut field = Self {
+            movement: *movement,
+            wrap: map.wrap,
+            targets: targets.to_vec(),
+            costs: Array::from_elem((map.width, map.height), UNREACHED),
+            directions: Array::from_elem((map.width, map.height), None),
+        };
+
+        let frontier = field.seed_targets(&|_| true);
+        field.flood(map, frontier, available);
+        field
+    }
+
+    pub fn targets(&self) -> &[(usize, usize)] {
+        &self.targets
+    }
+
+    /// Cost to reach the nearest target from loc (same as shortest_path_with would give).  None
+    /// if no target can be reached (or loc is off the map).  A target on a tile which cannot be
+    /// entered is only reached from itself.
+    pub fn cost(&self, loc: (usize, usize)) -> Option<usize> {
+        self.costs.get(loc).copied().filter(|cost| *cost != UNREACHED)
+    }
+
+    /// Which way to step from loc (e.g. (1, -1) is up and to the right).  None at a target or
+    /// when no target can be reached.
+    pub fn direction(&self, loc: (usize, usize)) -> Option<(isize, isize)> {
+        self.directions.get(loc).copied().flatten()
+    }
+
+    pub fn next_step(&self, loc: (usize, usize)) -> Option<(usize, usize)> {
+        self.direction(loc).and_then(|delta| self.offset(loc, delta))
+    }
+
+    /// Tiles at changed have been altered (e.g. a door closed or a bridge burned).  Every location
+    /// whose route passed through them is recomputed from the untouched locations around them.
+    pub fn update<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, changed: &[(usize, usize)],
+                                                        available: &dyn Fn(&T) -> usize) {
+        let mut invalid = HashSet::new();
+        let mut pending: Vec<(usize, usize)> = changed.iter().filter(|loc| self.costs.get(**loc).is_some()).copied().collect();
+
+        // Without corner cutting a changed tile also affects diagonal steps made beside it.
+        if !self.movement.corner_cutting {
+            for loc in changed {
+                pending.extend(self.around(*loc).filter(|next| {
+                    self.next_step(*next).is_some_and(|to| {
+                        let (dx, dy) = self.wrap.delta(loc, &to, self.costs.dim().0, self.costs.dim().1);
+                        dx.abs() <= 1 && dy.abs() <= 1
+                    })
+                }));
+            }
+        }
+
+        while let Some(loc) = pending.pop() {
+            if invalid.insert(loc) {
+                pending.extend(self.around(loc).filter(|next| self.next_step(*next) == Some(loc)));
+            }
+        }
+
+        for loc in &invalid {
+            self.costs[*loc] = UNREACHED;
+            self.directions[*loc] = None;
+        }
+
+        let mut frontier = self.seed_targets(&|loc| invalid.contains(loc));
+        for loc in &invalid {
+            for next in self.around(*loc).filter(|next| !invalid.contains(next)) {
+                if self.costs[next] != UNREACHED {
+                    frontier.push(Reverse((self.costs[next], next)));
+                }
+            }
+        }
+
+        self.flood(map, frontier, available);
+    }
+
+    fn offset(&self, loc: (usize, usize), delta: (isize, isize)) -> Option<(usize, usize)> {
+        let (width, height) = self.costs.dim();
+
+        self.wrap.normalize(loc.0 as isize + delta.0, loc.1 as isize + delta.1, width, height)
+    }
+
+    // Locations on the map around loc in all 8 directions.
+    fn around(&self, loc: (usize, usize)) -> impl Iterator<Item=(usize, usize)> + '_ {
+        (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|delta| *delta != (0, 0))
+            .filter_map(move |delta| self.offset(loc, delta))
+    }
+
+    fn seed_targets(&mut self, include: &dyn Fn(&(usize, usize)) -> bool) -> Frontier {
+        let mut frontier = BinaryHeap::new();
+
+        for target in self.targets.iter().filter(|target| include(target)) {
+            if let Some(cost) = self.costs.get_mut(*target) {
+                *cost = 0;
+                self.directions[*target] = None;
+                frontier.push(Reverse((0, *target)));
+            }
+        }
+
+        frontier
+    }
+
+    fn flood<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, mut frontier: Frontier,
+                                                   available: &dyn Fn(&T) -> usize) {
+        while let Some(Reverse((cost, loc))) = frontier.pop() {
+            if cost > self.costs[loc] {
+                continue
+            }
+
+            // Costs run towards the targets so what is paid is entering loc and not next.
+            let enter = available(&map.get(&loc).unwrap().solid);
+            if enter == 0 {
+                continue
+            }
+
+            for (next, _) in self.movement.neighbors(map, &loc, available) {
+                let delta = map.delta(&next, &loc);
+                let multiplier = if delta.0 != 0 && delta.1 != 0 { self.movement.diagonal_cost() } else { self.movement.straight_cost() };
+                let next_cost = cost + enter * multiplier;
+                if next_cost < self.costs[next] {
+                    self.costs[next] = next_cost;
+                    self.directions[next] = Some(delta);
+                    frontier.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::flow_field::FlowField;
+    use crate::map::generate_ascii_map;
+    use crate::{Map, Movement, Spot, Wrap};
+
+    const MAP: &str = "#######\n\
+                       #.....#\n\
+                       #.###.#\n\
+                       #.....#\n\
+                       #######";
+
+    fn available(tile: &char) -> usize {
+        match tile {
+            '.' => 1,
+            '~' => 4,
+            _ => 0,
+        }
+    }
+
+    fn assert_same(map: &Map<char, char>, field: &FlowField) {
+        let fresh = FlowField::new(map, &field.movement, field.targets(), &available);
+
+        for (loc, _) in map.iter() {
+            assert_eq!(field.cost(loc), fresh.cost(loc), "cost at {:?}", loc);
+            if let Some(next) = field.next_step(loc) {
+                let step = field.cost(loc).unwrap() - field.cost(next).unwrap();
+                assert!(step > 0, "direction at {:?} does not lead downhill", loc);
+            }
+        }
+    }
+
+    #[test]
+    fn test_directions() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let field = FlowField::new(&map, &Movement::EIGHT_WAY, &[(1, 1)], &available);
+
+        assert_eq!(field.cost((1, 1)), Some(0));
+        assert_eq!(field.direction((1, 1)), None);
+        assert_eq!(field.direction((2, 1)), Some((-1, 0)));
+        assert_eq!(field.direction((1, 2)), Some((0, -1)));
+        assert_eq!(field.cost((5, 3)), Some(5));
+        assert_eq!(field.cost((0, 0)), None);
+        assert_eq!(field.direction((0, 0)), None);
+
+        let mut loc = (5, 3);
+        let mut steps = 0;
+        while let Some(next) = field.next_step(loc) {
+            loc = next;
+            steps += 1;
+        }
+        assert_eq!((loc, steps), ((1, 1), 5));
+    }
+
+    #[test]
+    fn test_multiple_targets() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let field = FlowField::new(&map, &Movement::FOUR_WAY, &[(1, 1), (5, 3)], &available);
+
+        assert_eq!(field.cost((3, 1)), Some(2));
+        assert_eq!(field.next_step((5, 1)), Some((5, 2)));
+        assert_eq!(field.next_step((2, 3)), Some((1, 3)));
+    }
+
+    #[test]
+    fn test_update() {
+        let mut map = generate_ascii_map("map", MAP).unwrap();
+        let mut field = FlowField::new(&map, &Movement::EIGHT_WAY, &[(1, 1)], &available);
+
+        map.set(&(1, 2), Spot::new('#', None));
+        field.update(&map, &[(1, 2)], &available);
+        assert_eq!(field.cost((1, 3)), Some(8));
+        assert_eq!(field.next_step((1, 3)), Some((2, 3)));
+        assert_same(&map, &field);
+
+        map.set(&(1, 2), Spot::new('.', None));
+        field.update(&map, &[(1, 2)], &available);
+        assert_eq!(field.cost((1, 3)), Some(2));
+        assert_same(&map, &field);
+    }
+
+    #[test]
+    fn test_update_matches_rebuild() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut map = Map::new("map", 20, 15, &|_| '.');
+
+        for movement in [Movement::EIGHT_WAY, Movement::OCTILE] {
+            let mut field = FlowField::new(&map, &movement, &[(0, 0), (19, 7)], &available);
+
+            for _ in 0..40 {
+                let changed: Vec<(usize, usize)> = (0..3).map(|_| (rng.gen_range(0..20), rng.gen_range(0..15))).collect();
+                for loc in &changed {
+                    let tile = ['.', '#', '~'][rng.gen_range(0..3)];
+                    map.set(loc, Spot::new(tile, None));
+                }
+
+                field.update(&map, &changed, &available);
+                assert_same(&map, &field);
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_shortest_path() {
+        let map = generate_ascii_map("map", "~.....\n\
+                                            .~~.#.\n\
+                                            ...~..\n").unwrap();
+
+        for movement in [Movement::FOUR_WAY, Movement::EIGHT_WAY, Movement::OCTILE] {
+            let field = FlowField::new(&map, &movement, &[(0, 0)], &available);
+
+            assert_eq!(field.cost((1, 0)), Some(4 * movement.straight_cost()));
+            for (loc, spot) in map.iter().filter(|(_, spot)| spot.solid != '#') {
+                let expected = map.shortest_path_with(&movement, &loc, &(0, 0), &available).map(|(_, cost)| cost);
+                assert_eq!(field.cost(loc), expected, "cost at {:?} on {}", loc, spot.solid);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrap() {
+        let map: Map<char, char> = Map::new("map", 6, 1, &|_| '.').with_wrap(Wrap::HORIZONTAL);
+        let field = FlowField::new(&map, &Movement::FOUR_WAY, &[(0, 0)], &available);
+
+        assert_eq!(field.cost((5, 0)), Some(1));
+        assert_eq!(field.direction((5, 0)), Some((1, 0)));
+        assert_eq!(field.next_step((5, 0)), Some((0, 0)));
+        assert_eq!(field.cost((3, 0)), Some(3));
+    }
+}
@@ -0,0 +1,402 @@
+use ndarray::{Array, Axis, Ix2};
+use pathfinding::prelude::astar;
+use crate::{Overlay, Spot};
+
+/// Which way up each hex sits.  Storage uses offset coordinates (column, row) where pointy
+/// maps shove every odd row half a hex right and flat maps shove every odd column half a hex
+/// down.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HexOrientation {
+    POINTY,
+    FLAT,
+}
+
+/// Axial hex coordinates.  Unlike offset coordinates every hex has the same neighbors and
+/// distances are simple so all the hex math is done in these.
+///
+/// https://www.redblobgames.com/grids/hexagons/
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Axial {
+    pub q: isize,
+    pub r: isize,
+}
+
+impl Axial {
+    /// All six neighbouring directions starting from +q and going around clockwise (on a pointy
+    /// map that is east first).
+    pub const DIRECTIONS: [Axial; 6] = [
+        Axial { q: 1, r: 0 },
+        Axial { q: 0, r: 1 },
+        Axial { q: -1, r: 1 },
+        Axial { q: -1, r: 0 },
+        Axial { q: 0, r: -1 },
+        Axial { q: 1, r: -1 },
+    ];
+
+    pub fn new(q: isize, r: isize) -> Self {
+        Self { q, r }
+    }
+
+    /// Third cube coordinate (q + r + s is always 0).
+    pub fn s(&self) -> isize {
+        -self.q - self.r
+    }
+
+    pub fn neighbour(&self, direction: usize) -> Self {
+        let delta = Self::DIRECTIONS[direction % 6];
+
+        Self { q: self.q + delta.q, r: self.r + delta.r }
+    }
+
+    /// Fewest steps between two hexes.
+    pub fn distance(&self, other: &Axial) -> usize {
+        let (dq, dr, ds) = (self.q - other.q, self.r - other.r, self.s() - other.s());
+
+        dq.unsigned_abs().max(dr.unsigned_abs()).max(ds.unsigned_abs())
+    }
+
+    /// Every hex the line from the center of self to the center of other passes through (both
+    /// included).  Lines along the edge between two hexes are nudged to one side so the same
+    /// line always gives the same hexes.
+    pub fn line(&self, other: &Axial) -> Vec<Axial> {
+        let steps = self.distance(other);
+        let nudge = |value: isize, epsilon: f64| value as f64 + epsilon;
+        let (q0, r0) = (nudge(self.q, 1e-6), nudge(self.r, 2e-6));
+        let (q1, r1) = (nudge(other.q, 1e-6), nudge(other.r, 2e-6));
+
+        (0..=steps).map(|step| {
+            let t = if steps == 0 { 0.0 } else { step as f64 / steps as f64 };
+
+            Self::round(q0 + (q1 - q0) * t, r0 + (r1 - r0) * t)
+        }).collect()
+    }
+
+    // Nearest hex to a fractional location.
+    fn round(q: f64, r: f64) -> Axial {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+
+        Axial { q: rq as isize, r: rr as isize }
+    }
+}
+
+/// Map made of hexes storing the same Spot<T, I> as Map.  Locations are offset coordinates
+/// (see HexOrientation) so Overlays of the same width and height line up with it.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "HexMapData<T, I>"))]
+pub struct HexMap<T: PartialEq, I: Default + PartialEq> {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub orientation: HexOrientation,
+    map: Array<Spot<T, I>, Ix2>,
+}
+
+impl<T: PartialEq, I: Default + PartialEq> HexMap<T, I> {
+    pub fn new<S: Into<String>>(name: S, width: usize, height: usize, orientation: HexOrientation,
+                                default_fn: &dyn Fn((usize, usize)) -> T) -> Self {
+        Self {
+            name: name.into(),
+            width,
+            height,
+            orientation,
+            map: Array::from_shape_fn((width, height), |loc| Spot::new(default_fn(loc), None)),
+        }
+    }
+
+    pub fn create_overlay(&self) -> Overlay<bool> {
+        Overlay::new(self.width, self.height, false)
+    }
+
+    #[inline]
+    pub fn get(&self, loc: &(usize, usize)) -> Option<&Spot<T, I>> {
+        self.map.get(*loc)
+    }
+
+    pub fn get_mut(&mut self, loc: &(usize, usize)) -> Option<&mut Spot<T, I>> {
+        self.map.get_mut(*loc)
+    }
+
+    pub fn set(&mut self, loc: &(usize, usize), tile: Spot<T, I>) -> bool {
+        self.get_mut(loc).map(|spot| *spot = tile).is_some()
+    }
+
+    #[inline]
+    pub fn is_valid_loc(&self, loc: &(usize, usize)) -> bool {
+        loc.0 < self.width && loc.1 < self.height
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=((usize, usize), &Spot<T, I>)> + '_ {
+        (0..self.width * self.height).map(move |index| {
+            let loc = (index % self.width, index / self.width);
+            (loc, &self.map[loc])
+        })
+    }
+
+    pub fn to_axial(&self, loc: &(usize, usize)) -> Axial {
+        let (col, row) = (loc.0 as isize, loc.1 as isize);
+
+        match self.orientation {
+            HexOrientation::POINTY => Axial::new(col - (row - (row & 1)) / 2, row),
+            HexOrientation::FLAT => Axial::new(col, row - (col - (col & 1)) / 2),
+        }
+    }
+
+    /// Location of hex on this map.  None if it is off the map.
+    pub fn to_offset(&self, hex: &Axial) -> Option<(usize, usize)> {
+        let (col, row) = match self.orientation {
+            HexOrientation::POINTY => (hex.q + (hex.r - (hex.r & 1)) / 2, hex.r),
+            HexOrientation::FLAT => (hex.q, hex.r + (hex.q - (hex.q & 1)) / 2),
+        };
+
+        if col < 0 || row < 0 || !self.is_valid_loc(&(col as usize, row as usize)) {
+            None
+        } else {
+            Some((col as usize, row as usize))
+        }
+    }
+
+    pub fn distance(&self, a: &(usize, usize), b: &(usize, usize)) -> usize {
+        self.to_axial(a).distance(&self.to_axial(b))
+    }
+
+    /// Up to six locations touching loc (fewer at the edges of the map).
+    pub fn neighbors(&self, loc: &(usize, usize)) -> impl Iterator<Item=(usize, usize)> + '_ {
+        let hex = self.to_axial(loc);
+
+        (0..6).filter_map(move |direction| self.to_offset(&hex.neighbour(direction)))
+    }
+
+    // Neighbours which can be entered along with their cost.
+    fn adjacent_ats(&self, loc: &(usize, usize), available: &dyn Fn(&T) -> usize) -> Vec<((usize, usize), usize)> {
+        self.neighbors(loc)
+            .map(|next| (next, available(&self.map[next].solid)))
+            .filter(|(_, cost)| *cost != 0)
+            .collect()
+    }
+
+    /// A* across hexes using the same weight convention as Map::shortest_path (available gives 0
+    /// for impassable tiles and the cost to enter the tile otherwise).
+    pub fn shortest_path(&self, start: &(usize, usize), end: &(usize, usize), available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
+        let goal = self.to_axial(end);
+
+        astar(start,
+              |loc| self.adjacent_ats(loc, available),
+              |loc| self.to_axial(loc).distance(&goal),
+              |loc| loc == end)
+    }
+
+    /// Light every hex closer than radius (same as calculate_field_of_view) which can be seen
+    /// from start.  A hex is seen when every hex on the line between them is visible, so walls
+    /// themselves are seen but not what is behind them.
+    pub fn field_of_view(&self, start: &(usize, usize), radius: usize, light_map: &mut Overlay<bool>,
+                         visible: &dyn Fn(&Spot<T, I>) -> bool) {
+        light_map.reset();
+        if !self.is_valid_loc(start) {
+            return
+        }
+        light_map.set(*start, true);
+
+        let center = self.to_axial(start);
+        let reach = radius.saturating_sub(1) as isize;
+        for q in -reach..=reach {
+            for r in (-reach).max(-q - reach)..=reach.min(-q + reach) {
+                let hex = Axial::new(center.q + q, center.r + r);
+                let Some(loc) = self.to_offset(&hex) else {
+                    continue
+                };
+
+                let line = center.line(&hex);
+                let clear = line.iter().skip(1).take(line.len().saturating_sub(2))
+                    .all(|between| self.to_offset(between).is_some_and(|loc| visible(&self.map[loc])));
+                if clear {
+                    light_map.set(loc, true);
+                }
+            }
+        }
+    }
+
+    /// Draw the map with each row (pointy) or column (flat) offset the way the hexes sit.
+    /// Pointy maps are one line per row with odd rows indented.  Flat maps take two lines per
+    /// row with the odd columns on the lower line.
+    pub fn render(&self, glyph_fn: &dyn Fn(&Spot<T, I>) -> char) -> String {
+        let mut text = String::new();
+
+        for (row, line) in self.map.axis_iter(Axis(1)).enumerate() {
+            let glyphs: Vec<char> = line.iter().map(glyph_fn).collect();
+
+            match self.orientation {
+                HexOrientation::POINTY => {
+                    if row % 2 == 1 {
+                        text.push(' ');
+                    }
+                    text.push_str(&spaced(glyphs.iter().copied()));
+                }
+                HexOrientation::FLAT => {
+                    for half in 0..2 {
+                        let shown = glyphs.iter().enumerate().map(|(col, glyph)| if col % 2 == half { *glyph } else { ' ' });
+                        text.push_str(spaced(shown).trim_end());
+                        if half == 0 {
+                            text.push('\n');
+                        }
+                    }
+                }
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct HexMapData<T: PartialEq, I: Default + PartialEq> {
+    name: String,
+    width: usize,
+    height: usize,
+    orientation: HexOrientation,
+    map: Array<Spot<T, I>, Ix2>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: PartialEq, I: Default + PartialEq> TryFrom<HexMapData<T, I>> for HexMap<T, I> {
+    type Error = crate::Error;
+
+    fn try_from(data: HexMapData<T, I>) -> Result<Self, crate::Error> {
+        if data.map.dim() != (data.width, data.height) {
+            let (width, height) = data.map.dim();
+            return Err(crate::Error::InvalidDimensions(format!("{}x{} map has {}x{} hexes", data.width, data.height, width, height)))
+        }
+
+        Ok(HexMap { name: data.name, width: data.width, height: data.height, orientation: data.orientation, map: data.map })
+    }
+}
+
+fn spaced(glyphs: impl Iterator<Item=char>) -> String {
+    glyphs.map(String::from).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hex::{Axial, HexMap, HexOrientation};
+    use crate::Spot;
+
+    fn glyph(spot: &Spot<char, char>) -> char {
+        spot.solid
+    }
+
+    fn available(tile: &char) -> usize {
+        if *tile == '.' { 1 } else { 0 }
+    }
+
+    fn make_map(orientation: HexOrientation, walls: &[(usize, usize)]) -> HexMap<char, char> {
+        HexMap::new("map", 7, 5, orientation, &|loc| if walls.contains(&loc) { '#' } else { '.' })
+    }
+
+    #[test]
+    fn test_coordinates() {
+        for orientation in [HexOrientation::POINTY, HexOrientation::FLAT] {
+            let map = make_map(orientation, &[]);
+
+            for (loc, _) in map.iter() {
+                assert_eq!(map.to_offset(&map.to_axial(&loc)), Some(loc));
+            }
+            assert_eq!(map.to_offset(&Axial::new(-5, 0)), None);
+        }
+
+        let pointy = make_map(HexOrientation::POINTY, &[]);
+        assert_eq!(pointy.to_axial(&(2, 3)), Axial::new(1, 3));
+        let flat = make_map(HexOrientation::FLAT, &[]);
+        assert_eq!(flat.to_axial(&(3, 2)), Axial::new(3, 1));
+        assert_eq!(Axial::new(1, 3).s(), -4);
+    }
+
+    #[test]
+    fn test_neighbors_and_distance() {
+        let map = make_map(HexOrientation::POINTY, &[]);
+
+        let mut around: Vec<(usize, usize)> = map.neighbors(&(2, 1)).collect();
+        around.sort();
+        assert_eq!(around, vec![(1, 1), (2, 0), (2, 2), (3, 0), (3, 1), (3, 2)]);
+        let mut around: Vec<(usize, usize)> = map.neighbors(&(2, 2)).collect();
+        around.sort();
+        assert_eq!(around, vec![(1, 1), (1, 2), (1, 3), (2, 1), (2, 3), (3, 2)]);
+        assert_eq!(map.neighbors(&(0, 0)).count(), 2);
+
+        assert_eq!(map.distance(&(0, 0), &(6, 0)), 6);
+        assert_eq!(map.distance(&(0, 0), &(0, 4)), 4);
+        assert_eq!(map.distance(&(0, 0), &(2, 4)), 4);
+        assert_eq!(Axial::new(0, 0).line(&Axial::new(3, -3)).len(), 4);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let map = make_map(HexOrientation::FLAT, &[(3, 0), (3, 1), (3, 2), (3, 3)]);
+
+        let (path, cost) = map.shortest_path(&(1, 1), &(5, 1), &available).unwrap();
+        assert_eq!(path.last(), Some(&(5, 1)));
+        assert!(path.contains(&(3, 4)));
+        assert_eq!(cost, path.len() - 1);
+        assert!(path.windows(2).all(|step| map.distance(&step[0], &step[1]) == 1));
+
+        let (_, cost) = map.shortest_path(&(0, 0), &(2, 4), &available).unwrap();
+        assert_eq!(cost, map.distance(&(0, 0), &(2, 4)));
+
+        let blocked = make_map(HexOrientation::FLAT, &[(3, 0), (3, 1), (3, 2), (3, 3), (3, 4)]);
+        assert!(blocked.shortest_path(&(1, 1), &(5, 1), &available).is_none());
+    }
+
+    #[test]
+    fn test_field_of_view() {
+        let map = make_map(HexOrientation::POINTY, &[(3, 2)]);
+        let mut light_map = map.create_overlay();
+        let visible = |spot: &Spot<char, char>| spot.solid == '.';
+
+        map.field_of_view(&(1, 2), 5, &mut light_map, &visible);
+        assert!(*light_map.get((3, 2)).unwrap());
+        assert!(!*light_map.get((4, 2)).unwrap());
+        assert!(!*light_map.get((5, 2)).unwrap());
+        assert!(*light_map.get((4, 0)).unwrap());
+        assert!(*light_map.get((0, 4)).unwrap());
+
+        // Radius limits by hex distance.
+        map.field_of_view(&(3, 0), 2, &mut light_map, &visible);
+        assert_eq!(light_map.count(&|lit| *lit), 1 + map.neighbors(&(3, 0)).count());
+
+        map.field_of_view(&(7, 0), 3, &mut light_map, &visible);
+        assert_eq!(light_map.count(&|lit| *lit), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let map = make_map(HexOrientation::FLAT, &[(3, 2)]);
+        let json = serde_json::to_string(&map).unwrap();
+
+        let back: HexMap<char, char> = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.width, back.height, back.orientation), (7, 5, HexOrientation::FLAT));
+        assert_eq!(back.get(&(3, 2)).unwrap().solid, '#');
+
+        let error = serde_json::from_str::<HexMap<char, char>>(&json.replace("\"height\":5", "\"height\":6")).err().unwrap();
+        assert!(error.to_string().starts_with("invalid dimensions: 7x6 map has 7x5 hexes"));
+    }
+
+    #[test]
+    fn test_render() {
+        let map: HexMap<char, char> = HexMap::new("map", 3, 2, HexOrientation::POINTY, &|(x, y)| ['a', 'b', 'c', 'd', 'e', 'f'][x + y * 3]);
+        assert_eq!(map.render(&glyph), "a b c\n d e f\n");
+
+        let map: HexMap<char, char> = HexMap::new("map", 3, 2, HexOrientation::FLAT, &|(x, y)| ['a', 'b', 'c', 'd', 'e', 'f'][x + y * 3]);
+        assert_eq!(map.render(&glyph), "a   c\n  b\nd   f\n  e\n");
+    }
+}
@@ -1,18 +1,38 @@
+pub mod ascii_map;
 pub mod rectangle;
 pub mod builders;
+mod error;
+mod dijkstra_map;
 mod field_of_view;
+mod flow_field;
+mod hex;
+mod lighting;
+mod line;
 mod overlay;
 pub mod map;
+mod memory;
+mod path;
+mod region;
+mod render;
 pub mod spot;
 
-pub use map::Map;
-pub use overlay::Overlay;
+pub use map::{Map, Wrap};
+pub use overlay::{BitOverlay, Overlay};
 pub use rectangle::{Rectangle, RectangleIteratorType};
 pub use spot::Spot;
-pub use field_of_view::calculate_field_of_view;
-
-#[derive(Debug)]
-pub struct MyError {}
+pub use field_of_view::{calculate_cone_field_of_view, calculate_field_of_view, calculate_field_of_view_using,
+                        calculate_octant_field_of_view, cone_octants, DiamondWalls, FieldOfView, FovAlgorithm,
+                        PrecisePermissive, RecursiveShadowcasting, SymmetricShadowcasting};
+pub use dijkstra_map::{DijkstraMap, UNREACHABLE};
+pub use error::Error;
+pub use region::{Regions, NO_REGION};
+pub use render::MapRenderer;
+pub use flow_field::FlowField;
+pub use hex::{Axial, HexMap, HexOrientation};
+pub use lighting::{Falloff, LightSource, Lighting};
+pub use line::{BresenhamLine, SupercoverLine};
+pub use memory::MapMemory;
+pub use path::{Heuristic, HierarchicalMap, Movement, PartialPath, PathAlgorithm, SearchLimit};
 
 #[inline]
 pub const fn math_is_hard(x: usize, d: isize) -> Option<usize> {
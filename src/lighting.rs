@@ -0,0 +1,317 @@
+use crate::{calculate_field_of_view, Error, Map, Overlay, Spot};
+
+/// How quickly a light dims between its position and the edge of its radius.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    /// Full intensity all the way to the edge.
+    CONSTANT,
+    /// Dims evenly to nothing at the edge.
+    LINEAR,
+    /// Stays bright near the light and then drops off quickly.
+    QUADRATIC,
+}
+
+impl Falloff {
+    /// Fraction of intensity left at distance / radius of t (0.0 to 1.0).
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Falloff::CONSTANT => 1.0,
+            Falloff::LINEAR => 1.0 - t,
+            Falloff::QUADRATIC => (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSource {
+    pub position: (usize, usize),
+    pub radius: usize,
+    pub intensity: f32,
+    pub color: (u8, u8, u8),
+    pub falloff: Falloff,
+}
+
+impl LightSource {
+    pub fn new(position: (usize, usize), radius: usize, intensity: f32, color: (u8, u8, u8), falloff: Falloff) -> Self {
+        Self {
+            position,
+            radius,
+            intensity,
+            color,
+            falloff,
+        }
+    }
+}
+
+// Each light remembers what it added so it can be taken back out without redoing the others.
+struct Placed {
+    light: LightSource,
+    contribution: Vec<((usize, usize), f32)>,
+}
+
+/// Light levels and colors from every light placed on a map.  Lights are occluded with the same
+/// shadowcasting as calculate_field_of_view and overlapping lights add together.  Moving, changing
+/// or removing one light only recomputes that light.
+pub struct Lighting {
+    levels: Overlay<f32>,
+    colors: Overlay<[f32; 3]>,
+    lights: Vec<Option<Placed>>,
+    light_map: Overlay<bool>,
+}
+
+impl Lighting {
+    pub fn new<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>) -> Self {
+        Self {
+            levels: Overlay::new(map.width, map.height, 0.0),
+            colors: Overlay::new(map.width, map.height, [0.0; 3]),
+            lights: vec![],
+            light_map: map.create_overlay(),
+        }
+    }
+
+    /// Place a light and return the id used to change or remove it later.  visible says whether
+    /// light passes through a spot.
+    pub fn add<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, light: LightSource,
+                                                    visible: &dyn Fn(&Spot<T, I>) -> bool) -> Result<usize, Error> {
+        let placed = self.place(map, light, visible)?;
+        self.lights.push(Some(placed));
+
+        Ok(self.lights.len() - 1)
+    }
+
+    pub fn remove(&mut self, id: usize) -> Result<LightSource, Error> {
+        let placed = self.lights.get_mut(id).and_then(|placed| placed.take()).ok_or_else(|| unknown_light(id))?;
+        self.accumulate(&placed, -1.0);
+
+        Ok(placed.light)
+    }
+
+    /// Replace a light with a changed version of itself (moved, dimmed, recolored...).
+    pub fn update<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, id: usize, light: LightSource,
+                                                       visible: &dyn Fn(&Spot<T, I>) -> bool) -> Result<(), Error> {
+        let old = self.lights.get_mut(id).and_then(|placed| placed.take()).ok_or_else(|| unknown_light(id))?;
+        self.accumulate(&old, -1.0);
+
+        match self.place(map, light, visible) {
+            Ok(placed) => {
+                self.lights[id] = Some(placed);
+                Ok(())
+            }
+            Err(error) => {
+                // Leave things as they were.
+                self.accumulate(&old, 1.0);
+                self.lights[id] = Some(old);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn move_light<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, id: usize, position: (usize, usize),
+                                                           visible: &dyn Fn(&Spot<T, I>) -> bool) -> Result<(), Error> {
+        let mut light = *self.light(id).ok_or_else(|| unknown_light(id))?;
+        light.position = position;
+
+        self.update(map, id, light, visible)
+    }
+
+    /// Recompute every light.  Needed when the map itself changes since any light may now be
+    /// blocked differently.  The map may also have changed size but if a light no longer fits
+    /// on it OutOfBounds is returned and nothing changes.
+    pub fn recompute<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>,
+                                                          visible: &dyn Fn(&Spot<T, I>) -> bool) -> Result<(), Error> {
+        if let Some(placed) = self.lights.iter().flatten().find(|placed| !map.is_valid_loc(&placed.light.position)) {
+            return Err(Error::OutOfBounds { loc: placed.light.position, width: map.width, height: map.height })
+        }
+
+        self.levels = Overlay::new(map.width, map.height, 0.0);
+        self.colors = Overlay::new(map.width, map.height, [0.0; 3]);
+        self.light_map = map.create_overlay();
+
+        for index in 0..self.lights.len() {
+            if let Some(old) = self.lights[index].take() {
+                self.lights[index] = Some(self.place(map, old.light, visible)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn light(&self, id: usize) -> Option<&LightSource> {
+        self.lights.get(id).and_then(|placed| placed.as_ref()).map(|placed| &placed.light)
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item=(usize, &LightSource)> {
+        self.lights.iter().enumerate().filter_map(|(id, placed)| placed.as_ref().map(|placed| (id, &placed.light)))
+    }
+
+    /// Summed intensity of every light reaching loc.
+    pub fn level(&self, loc: (usize, usize)) -> f32 {
+        self.levels.get(loc).map_or(0.0, |level| level.max(0.0))
+    }
+
+    /// Mixed color of every light reaching loc scaled by how bright they are there.
+    pub fn color(&self, loc: (usize, usize)) -> (u8, u8, u8) {
+        let channel = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+
+        self.colors.get(loc).map_or((0, 0, 0), |[r, g, b]| (channel(*r), channel(*g), channel(*b)))
+    }
+
+    pub fn levels(&self) -> &Overlay<f32> {
+        &self.levels
+    }
+
+    pub fn colors(&self) -> &Overlay<[f32; 3]> {
+        &self.colors
+    }
+
+    fn place<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, light: LightSource,
+                                                  visible: &dyn Fn(&Spot<T, I>) -> bool) -> Result<Placed, Error> {
+        if (map.width, map.height) != (self.levels.width(), self.levels.height()) {
+            return Err(Error::InvalidArgument(format!("{}x{} map given to lighting for a {}x{} map (see recompute)",
+                                                      map.width, map.height, self.levels.width(), self.levels.height())))
+        }
+
+        if !map.is_valid_loc(&light.position) {
+            return Err(Error::OutOfBounds { loc: light.position, width: map.width, height: map.height })
+        }
+
+        calculate_field_of_view(map, &light.position, light.radius, &mut self.light_map, visible);
+
+        let contribution = self.light_map.iter()
+            .filter(|(_, lit)| **lit)
+            .map(|(loc, _)| {
+                let (dx, dy) = map.delta(&light.position, &loc);
+                let t = ((dx * dx + dy * dy) as f32).sqrt() / light.radius.max(1) as f32;
+
+                (loc, light.intensity * light.falloff.apply(t))
+            })
+            .collect();
+        let placed = Placed { light, contribution };
+        self.accumulate(&placed, 1.0);
+
+        Ok(placed)
+    }
+
+    // Add (sign 1.0) or take away (sign -1.0) what a light contributes.
+    fn accumulate(&mut self, placed: &Placed, sign: f32) {
+        let (r, g, b) = placed.light.color;
+        let color = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
+
+        for (loc, amount) in placed.contribution.iter() {
+            let amount = amount * sign;
+            let level = *self.levels.get(*loc).unwrap();
+            let mut mixed = *self.colors.get(*loc).unwrap();
+
+            for (channel, value) in mixed.iter_mut().zip(color.iter()) {
+                *channel += value * amount;
+            }
+            self.levels.set(*loc, level + amount);
+            self.colors.set(*loc, mixed);
+        }
+    }
+}
+
+fn unknown_light(id: usize) -> Error {
+    Error::InvalidArgument(format!("no light with id {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lighting::{Falloff, LightSource, Lighting};
+    use crate::map::generate_ascii_map;
+    use crate::{Error, Spot};
+
+    const MAP: &str = ".........\n\
+                       ....#....\n\
+                       .........\n";
+
+    fn visible(spot: &Spot<char, char>) -> bool {
+        spot.solid == '.'
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn test_falloff() {
+        assert!(close(Falloff::CONSTANT.apply(0.5), 1.0));
+        assert!(close(Falloff::LINEAR.apply(0.25), 0.75));
+        assert!(close(Falloff::QUADRATIC.apply(0.5), 0.25));
+        assert!(close(Falloff::LINEAR.apply(2.0), 0.0));
+    }
+
+    #[test]
+    fn test_single_light() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let mut lighting = Lighting::new(&map);
+        lighting.add(&map, LightSource::new((2, 1), 4, 2.0, (255, 128, 0), Falloff::LINEAR), &visible).unwrap();
+
+        assert!(close(lighting.level((2, 1)), 2.0));
+        assert!(close(lighting.level((4, 1)), 1.0));
+        assert!(close(lighting.level((5, 1)), 0.0)); // behind the wall
+        assert!(close(lighting.level((8, 1)), 0.0)); // out of range
+        assert_eq!(lighting.color((2, 1)), (255, 255, 0));
+        assert_eq!(lighting.color((3, 1)), (255, 192, 0));
+    }
+
+    #[test]
+    fn test_lights_add_together() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let mut lighting = Lighting::new(&map);
+        lighting.add(&map, LightSource::new((0, 0), 3, 0.5, (255, 0, 0), Falloff::CONSTANT), &visible).unwrap();
+        lighting.add(&map, LightSource::new((1, 0), 3, 0.5, (0, 0, 255), Falloff::CONSTANT), &visible).unwrap();
+
+        assert!(close(lighting.level((0, 1)), 1.0));
+        assert_eq!(lighting.color((0, 1)), (128, 0, 128));
+    }
+
+    #[test]
+    fn test_incremental_matches_recompute() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let mut lighting = Lighting::new(&map);
+        let torch = lighting.add(&map, LightSource::new((0, 1), 5, 1.0, (255, 200, 100), Falloff::QUADRATIC), &visible).unwrap();
+        let lava = lighting.add(&map, LightSource::new((8, 2), 3, 0.7, (255, 64, 0), Falloff::LINEAR), &visible).unwrap();
+
+        lighting.move_light(&map, torch, (6, 0), &visible).unwrap();
+        let moved: Vec<f32> = lighting.levels().iter().map(|(_, level)| *level).collect();
+
+        lighting.recompute(&map, &visible).unwrap();
+        for (level, (loc, _)) in moved.iter().zip(lighting.levels().iter()) {
+            assert!(close(*level, lighting.level(loc)), "{:?}", loc);
+        }
+
+        assert_eq!(lighting.remove(lava).unwrap().position, (8, 2));
+        assert_eq!(lighting.lights().map(|(id, _)| id).collect::<Vec<_>>(), vec![torch]);
+        assert!(close(lighting.level((8, 2)), Falloff::QUADRATIC.apply(8.0_f32.sqrt() / 5.0)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let mut lighting = Lighting::new(&map);
+        let light = LightSource::new((9, 0), 3, 1.0, (255, 255, 255), Falloff::CONSTANT);
+
+        assert_eq!(lighting.add(&map, light, &visible).err(), Some(Error::OutOfBounds { loc: (9, 0), width: 9, height: 3 }));
+        assert!(lighting.remove(0).is_err());
+
+        let id = lighting.add(&map, LightSource { position: (1, 1), ..light }, &visible).unwrap();
+        assert!(lighting.move_light(&map, id, (20, 20), &visible).is_err());
+        assert_eq!(lighting.light(id).unwrap().position, (1, 1));
+        assert!(close(lighting.level((1, 1)), 1.0));
+
+        // A light which would be off a smaller map leaves everything as it was.
+        let small = generate_ascii_map("map", "..\n").unwrap();
+        assert!(lighting.add(&small, LightSource { position: (0, 0), ..light }, &visible).is_err());
+        assert_eq!(lighting.recompute(&small, &visible), Err(Error::OutOfBounds { loc: (1, 1), width: 2, height: 1 }));
+        assert!(close(lighting.level((1, 1)), 1.0));
+
+        let larger = generate_ascii_map("map", "...\n...\n...\n...\n").unwrap();
+        lighting.recompute(&larger, &visible).unwrap();
+        assert_eq!((lighting.levels().width(), lighting.levels().height()), (3, 4));
+        assert!(close(lighting.level((2, 3)), 1.0));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{Map, Spot};
+
+/// Bresenham line from start to end including both.  Exactly one location per step along the
+/// longer axis so lines are thin and diagonal steps can slip between two walls touching at a
+/// corner.
+pub struct BresenhamLine {
+    current: (isize, isize),
+    end: (isize, isize),
+    delta: (isize, isize),
+    step: (isize, isize),
+    error: isize,
+    done: bool,
+}
+
+impl BresenhamLine {
+    pub fn new(start: (usize, usize), end: (usize, usize)) -> Self {
+        let (x0, y0, x1, y1) = (start.0 as isize, start.1 as isize, end.0 as isize, end.1 as isize);
+        let delta = ((x1 - x0).abs(), -(y1 - y0).abs());
+
+        Self {
+            current: (x0, y0),
+            end: (x1, y1),
+            delta,
+            step: ((x1 - x0).signum(), (y1 - y0).signum()),
+            error: delta.0 + delta.1,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for BresenhamLine {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+
+        let (x, y) = self.current;
+        if self.current == self.end {
+            self.done = true;
+        } else {
+            let error = 2 * self.error;
+            if error >= self.delta.1 {
+                self.error += self.delta.1;
+                self.current.0 += self.step.0;
+            }
+            if error <= self.delta.0 {
+                self.error += self.delta.0;
+                self.current.1 += self.step.1;
+            }
+        }
+
+        Some((x as usize, y as usize))
+    }
+}
+
+/// Every location the line between the centers of start and end passes through.  When it passes
+/// exactly through a corner both locations beside the corner are included, so nothing can slip
+/// through a gap between two diagonal walls.
+pub struct SupercoverLine {
+    current: (isize, isize),
+    steps: (isize, isize),
+    taken: (isize, isize),
+    step: (isize, isize),
+    // Locations beside a corner still to hand out, last first.
+    pending: Vec<(isize, isize)>,
+    done: bool,
+}
+
+impl SupercoverLine {
+    pub fn new(start: (usize, usize), end: (usize, usize)) -> Self {
+        let (x0, y0, x1, y1) = (start.0 as isize, start.1 as isize, end.0 as isize, end.1 as isize);
+
+        Self {
+            current: (x0, y0),
+            steps: ((x1 - x0).abs(), (y1 - y0).abs()),
+            taken: (0, 0),
+            step: ((x1 - x0).signum(), (y1 - y0).signum()),
+            pending: vec![],
+            done: false,
+        }
+    }
+}
+
+impl Iterator for SupercoverLine {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((x, y)) = self.pending.pop() {
+            return Some((x as usize, y as usize))
+        }
+
+        if self.done {
+            return None
+        }
+
+        let (x, y) = self.current;
+        if self.taken == self.steps {
+            self.done = true;
+            return Some((x as usize, y as usize))
+        }
+
+        // Compare where the line crosses the next vertical and horizontal grid lines.
+        let (nx, ny) = self.steps;
+        let (ix, iy) = self.taken;
+        let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+        if decision == 0 {
+            self.pending.push((x, y + self.step.1));
+            self.pending.push((x + self.step.0, y));
+            self.current = (x + self.step.0, y + self.step.1);
+            self.taken = (ix + 1, iy + 1);
+        } else if decision < 0 {
+            self.current.0 += self.step.0;
+            self.taken.0 += 1;
+        } else {
+            self.current.1 += self.step.1;
+            self.taken.1 += 1;
+        }
+
+        Some((x as usize, y as usize))
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// Whether a can see b without computing a whole field of view.  Only the locations between
+    /// them need to be visible so a wall can still be seen.  Every location the line touches
+    /// (see SupercoverLine) is checked so a can see b exactly when b can see a and sight never
+    /// slips between two walls touching at a corner.  On a wrapping map the line goes across
+    /// the edge when that is shorter.
+    pub fn has_line_of_sight(&self, a: &(usize, usize), b: &(usize, usize), visible: &dyn Fn(&Spot<T, I>) -> bool) -> bool {
+        if !self.is_valid_loc(a) || !self.is_valid_loc(b) {
+            return false
+        }
+
+        self.wrapped_line(a, b, SupercoverLine::new)
+            .filter(|loc| loc != a && loc != b)
+            .all(|loc| visible(self.get(&loc).unwrap()))
+    }
+
+    /// Follow a projectile from start towards end and return the first location which stops it
+    /// (end included) or the first location off the map.  None means it reached end unblocked.
+    /// On a wrapping map it flies across the edge when that is shorter.
+    pub fn trace_projectile(&self, start: &(usize, usize), end: &(usize, usize),
+                            visible: &dyn Fn(&Spot<T, I>) -> bool) -> Option<(usize, usize)> {
+        self.wrapped_line(start, end, BresenhamLine::new)
+            .skip(1)
+            .find(|loc| self.get(loc).is_none_or(|spot| !visible(spot)))
+    }
+
+    // Line from a to b along the shortest delta between them.  It is drawn shifted far enough
+    // from 0 that going across an edge stays positive and then moved back onto the map.
+    // Locations off an edge which does not wrap are left where they are.
+    fn wrapped_line<'a, L: Iterator<Item=(usize, usize)> + 'a>(&'a self, a: &(usize, usize), b: &(usize, usize),
+                                                               line: fn((usize, usize), (usize, usize)) -> L) -> impl Iterator<Item=(usize, usize)> + 'a {
+        let (dx, dy) = self.delta(a, b);
+        let origin = (self.width.max(a.0) as isize, self.height.max(a.1) as isize);
+        let a = *a;
+
+        line((origin.0 as usize, origin.1 as usize), ((origin.0 + dx) as usize, (origin.1 + dy) as usize))
+            .map(move |(x, y)| {
+                let delta = (x as isize - origin.0, y as isize - origin.1);
+
+                self.offset(&a, &delta).unwrap_or(((a.0 as isize + delta.0) as usize, (a.1 as isize + delta.1) as usize))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line::{BresenhamLine, SupercoverLine};
+    use crate::map::generate_ascii_map;
+    use crate::{Spot, Wrap};
+
+    fn visible(spot: &Spot<char, char>) -> bool {
+        spot.solid == '.'
+    }
+
+    #[test]
+    fn test_bresenham() {
+        assert_eq!(BresenhamLine::new((0, 0), (4, 2)).collect::<Vec<_>>(), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(BresenhamLine::new((3, 3), (3, 0)).collect::<Vec<_>>(), vec![(3, 3), (3, 2), (3, 1), (3, 0)]);
+        assert_eq!(BresenhamLine::new((2, 2), (0, 0)).collect::<Vec<_>>(), vec![(2, 2), (1, 1), (0, 0)]);
+        assert_eq!(BresenhamLine::new((1, 1), (1, 1)).collect::<Vec<_>>(), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_supercover() {
+        assert_eq!(SupercoverLine::new((0, 0), (2, 2)).collect::<Vec<_>>(),
+                   vec![(0, 0), (1, 0), (0, 1), (1, 1), (2, 1), (1, 2), (2, 2)]);
+        assert_eq!(SupercoverLine::new((0, 0), (3, 1)).collect::<Vec<_>>(), vec![(0, 0), (1, 0), (2, 0), (1, 1), (2, 1), (3, 1)]);
+        assert_eq!(SupercoverLine::new((4, 1), (0, 0)).collect::<Vec<_>>(), vec![(4, 1), (3, 1), (2, 1), (2, 0), (1, 0), (0, 0)]);
+        assert_eq!(SupercoverLine::new((1, 1), (1, 1)).collect::<Vec<_>>(), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let map = generate_ascii_map("map", "......\n\
+                                              ..#...\n\
+                                              ......\n").unwrap();
+
+        assert!(map.has_line_of_sight(&(0, 1), &(1, 1), &visible));
+        assert!(map.has_line_of_sight(&(0, 1), &(2, 1), &visible));
+        assert!(!map.has_line_of_sight(&(0, 1), &(4, 1), &visible));
+        assert!(map.has_line_of_sight(&(0, 0), &(5, 0), &visible));
+        assert!(!map.has_line_of_sight(&(0, 0), &(4, 2), &visible));
+        assert!(!map.has_line_of_sight(&(0, 0), &(6, 2), &visible));
+    }
+
+    #[test]
+    fn test_line_of_sight_symmetric() {
+        let map = generate_ascii_map("map", ".#....#.\n\
+                                              #..#....\n\
+                                              ...#..#.\n\
+                                              .#...#..\n").unwrap();
+
+        // Squeezing between the walls at (0, 1) and (1, 0) is not allowed either way.
+        assert!(!map.has_line_of_sight(&(0, 0), &(1, 1), &visible));
+        assert!(!map.has_line_of_sight(&(1, 1), &(0, 0), &visible));
+
+        for (a, _) in map.iter() {
+            for (b, _) in map.iter() {
+                assert_eq!(map.has_line_of_sight(&a, &b, &visible), map.has_line_of_sight(&b, &a, &visible), "{:?} {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_of_sight_wrap() {
+        let map = generate_ascii_map("map", "..#.....\n\
+                                              ......#.\n").unwrap().with_wrap(Wrap::HORIZONTAL);
+
+        // Looking west across the edge rather than east through the wall.
+        assert!(map.has_line_of_sight(&(0, 0), &(6, 0), &visible));
+        assert!(map.has_line_of_sight(&(6, 0), &(0, 0), &visible));
+        assert!(map.has_line_of_sight(&(2, 1), &(7, 1), &visible));
+        assert!(!map.has_line_of_sight(&(0, 1), &(5, 1), &visible));
+    }
+
+    #[test]
+    fn test_trace_projectile() {
+        let map = generate_ascii_map("map", "......\n\
+                                              ..#...\n\
+                                              ......\n").unwrap();
+
+        assert_eq!(map.trace_projectile(&(0, 1), &(5, 1), &visible), Some((2, 1)));
+        assert_eq!(map.trace_projectile(&(0, 1), &(2, 1), &visible), Some((2, 1)));
+        assert_eq!(map.trace_projectile(&(0, 0), &(5, 0), &visible), None);
+        assert_eq!(map.trace_projectile(&(4, 0), &(7, 0), &visible), Some((6, 0)));
+    }
+
+    #[test]
+    fn test_trace_projectile_wrap() {
+        let mut map = generate_ascii_map("map", "..#.....\n").unwrap().with_wrap(Wrap::HORIZONTAL);
+
+        // Flying west across the edge rather than east into the wall.
+        assert_eq!(map.trace_projectile(&(0, 0), &(6, 0), &visible), None);
+        assert_eq!(map.trace_projectile(&(0, 0), &(3, 0), &visible), Some((2, 0)));
+
+        map.set(&(7, 0), Spot::new('#', None));
+        assert_eq!(map.trace_projectile(&(1, 0), &(6, 0), &visible), Some((7, 0)));
+    }
+}
@@ -1,18 +1,84 @@
-use ndarray::{Array, Ix2};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use ndarray::{Array, Axis, Ix2};
 use pathfinding::prelude::astar;
-use rand::{Rng, thread_rng};
-use crate::{add_delta, Overlay, Rectangle, Spot};
+use rand::Rng;
+use crate::{Error, Movement, Overlay, Rectangle, Spot};
 
 // T: solid, I: item(s)
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// Deserialize goes through a private XData mirror (here, BitOverlay and HexMap) so sizes
+// are checked before the value can be used.
+#[cfg_attr(feature = "serde", serde(try_from = "MapData<T, I>"))]
 pub struct Map<T: PartialEq, I: Default + PartialEq> {
     pub name: String,
     pub width: usize,
     pub height: usize,
     // FIXME: A trait for different shape rooms is desired here but until I understand what the needs are we will use one struct
     pub rooms: Vec<Rectangle>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wrap: Wrap,
     map: Array<Spot<T, I>, Ix2>,
 }
 
+/// Which edges of a map join up with the opposite edge.  Locations themselves are always within
+/// the map but stepping off a wrapping edge arrives on the other side (e.g. an overworld which
+/// wraps horizontally like a globe).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Wrap {
+    #[default]
+    NONE,
+    HORIZONTAL,
+    VERTICAL,
+    BOTH,
+}
+
+impl Wrap {
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Wrap::HORIZONTAL | Wrap::BOTH)
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, Wrap::VERTICAL | Wrap::BOTH)
+    }
+
+    /// Bring (x, y) back within width and height along the wrapping axes.  None if it is off
+    /// the edge of an axis which does not wrap (or there is no location at all).
+    #[inline]
+    pub fn normalize(&self, x: isize, y: isize, width: usize, height: usize) -> Option<(usize, usize)> {
+        if width == 0 || height == 0 {
+            return None
+        }
+
+        let x = if self.is_horizontal() { x.rem_euclid(width as isize) } else { x };
+        let y = if self.is_vertical() { y.rem_euclid(height as isize) } else { y };
+
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            None
+        } else {
+            Some((x as usize, y as usize))
+        }
+    }
+
+    /// Shortest (dx, dy) from one location to another.  Along a wrapping axis this may go
+    /// across the edge.
+    pub fn delta(&self, from: &(usize, usize), to: &(usize, usize), width: usize, height: usize) -> (isize, isize) {
+        let shortest = |from: usize, to: usize, size: usize, wraps: bool| {
+            let delta = to as isize - from as isize;
+            let size = size as isize;
+
+            if wraps && delta.abs() * 2 > size {
+                delta - delta.signum() * size
+            } else {
+                delta
+            }
+        };
+
+        (shortest(from.0, to.0, width, self.is_horizontal()), shortest(from.1, to.1, height, self.is_vertical()))
+    }
+}
+
 struct MapIterator<'a, T: PartialEq, I: Default + PartialEq> {
     map: &'a Map<T, I>,
     index: usize,
@@ -97,12 +163,10 @@ impl<'a, T: PartialEq, I: Default + PartialEq, U: PartialEq> Iterator for CoordI
                 let delta = POINTS[self.index];
                 self.index += 1;
 
-                if let Some(loc) = add_delta(&self.loc, &delta) {
-                    if let Some(tile) = self.map.get(&loc) {
-                        let test = (self.available)(&tile.solid);
-                        if test != self.invalid {
-                            return Some((loc, test))
-                        }
+                if let Some(loc) = self.map.offset(&self.loc, &delta) {
+                    let test = (self.available)(&self.map.get(&loc).unwrap().solid);
+                    if test != self.invalid {
+                        return Some((loc, test))
                     }
                 }
             }
@@ -112,12 +176,10 @@ impl<'a, T: PartialEq, I: Default + PartialEq, U: PartialEq> Iterator for CoordI
                 let delta = SIMPLE_POINTS[self.index];
                 self.index += 1;
 
-                if let Some(loc) = add_delta(&self.loc, &delta) {
-                    if let Some(tile) = self.map.get(&loc) {
-                        let test = (self.available)(&tile.solid);
-                        if test != self.invalid {
-                            return Some((loc, test))
-                        }
+                if let Some(loc) = self.map.offset(&self.loc, &delta) {
+                    let test = (self.available)(&self.map.get(&loc).unwrap().solid);
+                    if test != self.invalid {
+                        return Some((loc, test))
                     }
                 }
             }
@@ -128,19 +190,23 @@ impl<'a, T: PartialEq, I: Default + PartialEq, U: PartialEq> Iterator for CoordI
     }
 }
 
-pub fn generate_ascii_map<S: Into<String>>(name: S, ascii_map: &str) -> Result<Map<char, char>, ()> {
+pub fn generate_ascii_map<S: Into<String>>(name: S, ascii_map: &str) -> Result<Map<char, char>, Error> {
     let rows: Vec<&str> = ascii_map.split_terminator('\n').collect();
     let height = rows.len();
 
     if height == 0 {
-        return Err(())
+        return Err(Error::Malformed { line: 1, column: 1, message: "empty map".to_string() })
     }
 
     let width = rows[0].len();
 
     // verify all lines are same length;
-    if let Some(_) = rows.iter().find(|e| e.len() != width) {
-        return Err(())
+    if let Some((index, row)) = rows.iter().enumerate().find(|(_, e)| e.len() != width) {
+        return Err(Error::Malformed {
+            line: index + 1,
+            column: width.min(row.len()) + 1,
+            message: "row width differs from first row".to_string(),
+        })
     }
 
     let default_fn = |_| '.';
@@ -170,10 +236,16 @@ impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
             width,
             height,
             rooms: vec![],
+            wrap: Wrap::NONE,
             map: Array::<Spot<T, I>, Ix2>::from_shape_fn((width, height), default),
         }
     }
 
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
     pub fn add_room(&mut self, rect: Rectangle) {
         self.rooms.push(rect);
     }
@@ -208,6 +280,24 @@ impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
         found
     }
 
+    /// (x, y) as a location on this map taking wrapping into account.  None if it is off the map.
+    #[inline]
+    pub fn wrap_loc(&self, x: isize, y: isize) -> Option<(usize, usize)> {
+        self.wrap.normalize(x, y, self.width, self.height)
+    }
+
+    /// Location one step of delta away from loc (across the edge if the map wraps there).
+    #[inline]
+    pub fn offset(&self, loc: &(usize, usize), delta: &(isize, isize)) -> Option<(usize, usize)> {
+        self.wrap_loc(loc.0 as isize + delta.0, loc.1 as isize + delta.1)
+    }
+
+    /// Shortest (dx, dy) from one location to another (see Wrap::delta).
+    #[inline]
+    pub fn delta(&self, from: &(usize, usize), to: &(usize, usize)) -> (isize, isize) {
+        self.wrap.delta(from, to, self.width, self.height)
+    }
+
     /// Note: Assumes all index accesses will get an index from a method which will prepare
     /// a safe index.
     fn point_for(&self, index: usize) -> (usize, usize) {
@@ -216,13 +306,14 @@ impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
 
     // Assumes valid point
     #[inline]
-    fn adjacent_ats<'a>(&'a self, loc: &(usize, usize), available: &'a (dyn Fn(&T) -> usize + 'a)) -> impl Iterator<Item=((usize, usize), usize)> + 'a {
+    pub(crate) fn adjacent_ats<'a>(&'a self, loc: &(usize, usize), available: &'a (dyn Fn(&T) -> usize + 'a)) -> impl Iterator<Item=((usize, usize), usize)> + 'a {
         CoordIterator::new(self, loc, available, 0, true)
     }
 
+    // Assumes valid point
     #[inline]
-    fn distance(p1: &(usize, usize), p2: &(usize, usize)) -> usize {
-        p1.0.abs_diff(p2.0) + p1.1.abs_diff(p2.1)
+    pub(crate) fn adjacent_matching<'a>(&'a self, loc: &(usize, usize), test: &'a (dyn Fn(&T) -> bool + 'a), include_diagonals: bool) -> impl Iterator<Item=(usize, usize)> + 'a {
+        CoordIterator::new(self, loc, test, false, include_diagonals).map(|(loc, _)| loc)
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item=((usize, usize), &'a Spot<T, I>)> + 'a {
@@ -250,53 +341,93 @@ impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
         let iter = CoordIterator::new(self, loc, &test, false, include_diagonals);
 
         let mut result = 0;
-        for ((x, y), _) in iter {
-            let (dx, dy) = (x as isize - loc.0 as isize, y as isize - loc.1 as isize);
-            let bits = 1 << ((dx*-1 + 1) + ((dy*-1 + 1) * 3));
+        for (next, _) in iter {
+            let (dx, dy) = self.delta(loc, &next);
+            let bits = 1 << ((-dx + 1) + ((-dy + 1) * 3));
 
-            result = result | bits
+            result |= bits
         }
         result
     }
 
+    /// Cheapest path moving in any of the 8 directions.  See shortest_path_with for other
+    /// movement rules.
     pub fn shortest_path(&self, start: &(usize, usize), end: &(usize, usize), available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
-        astar(&start,
+        // Chebyshev since a diagonal step costs the same as a straight one.
+        astar(start,
               |i| self.adjacent_ats(i, available),
-              |i| Self::distance(i, end),
+              |i| Movement::EIGHT_WAY.estimate_on(self, i, end),
               |i| i == end)
     }
 
-    pub fn find_random_tile_loc(&self, available: &dyn Fn(&Spot<T, I>) -> bool) -> Result<(usize, usize), ()> {
+    /// Pick a random room and then try to find a location within it which is available.
+    pub fn find_random_tile_loc<R: Rng + ?Sized>(&self, rng: &mut R, available: &dyn Fn(&Spot<T, I>) -> bool) -> Result<(usize, usize), Error> {
         let room_count = self.rooms.len();
-        let room_index = thread_rng().gen_range(0..room_count);
+        if room_count == 0 {
+            return Err(Error::GenerationFailed("map has no rooms".to_string()))
+        }
+        let room_index = rng.gen_range(0..room_count);
         let room = self.rooms.get(room_index).unwrap();
 
         for _ in 0..100 {
-            let loc = (room.random_x(false), room.random_y(false));
-            if (available)(&self.map.get(loc).unwrap()) {
+            let loc = (room.random_x(rng, false), room.random_y(rng, false));
+            if (available)(self.map.get(loc).unwrap()) {
                 return Ok(loc)
             }
         }
 
-        Err(())
+        Err(Error::GenerationFailed("no available tile found".to_string()))
     }
 }
 
-/*
-impl<T: Clone + PartialEq> Display for Map<T> {
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct MapData<T: PartialEq, I: Default + PartialEq> {
+    name: String,
+    width: usize,
+    height: usize,
+    rooms: Vec<Rectangle>,
+    #[serde(default)]
+    wrap: Wrap,
+    map: Array<Spot<T, I>, Ix2>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: PartialEq, I: Default + PartialEq> TryFrom<MapData<T, I>> for Map<T, I> {
+    type Error = Error;
+
+    fn try_from(data: MapData<T, I>) -> Result<Self, Error> {
+        if data.map.dim() != (data.width, data.height) {
+            let (width, height) = data.map.dim();
+            return Err(Error::InvalidDimensions(format!("{}x{} map has {}x{} tiles", data.width, data.height, width, height)))
+        }
+
+        if let Some(room) = data.rooms.iter().find(|room| room.lrc.0 >= data.width || room.lrc.1 >= data.height) {
+            return Err(Error::OutOfBounds { loc: room.lrc, width: data.width, height: data.height })
+        }
+
+        Ok(Map { name: data.name, width: data.width, height: data.height, rooms: data.rooms, wrap: data.wrap, map: data.map })
+    }
+}
+
+impl<T: PartialEq + Display, I: Default + PartialEq> Display for Map<T, I> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for line in self.map.axis_iter(Axis(1)) {
-            let line: String = line.iter().map(|tile| tile.id).collect();
-            let _ = writeln!(f, "{}", line);
+            for spot in line.iter() {
+                write!(f, "{}", spot.solid)?;
+            }
+            writeln!(f)?;
         }
         Ok(())
     }
-}*/
+}
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use crate::{Map, Rectangle, Spot};
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, SeedableRng};
+    use crate::{Error, Map, Rectangle, Spot, Wrap};
     use crate::map::generate_ascii_map;
 
     #[test]
@@ -307,9 +438,24 @@ mod tests {
         let mut map = Map::new("map", 3, 3, &|_| '.');
         let room = Rectangle::new(0, 0, 3, 3).unwrap();
         map.add_room(room);
-        assert!(map.find_random_tile_loc(&|c: &Spot<char, char>| c.solid == '.').is_ok());
+        assert!(map.find_random_tile_loc(&mut thread_rng(), &|c: &Spot<char, char>| c.solid == '.').is_ok());
 
-        assert!(map.find_random_tile_loc(&|c: &Spot<char, char>| c.solid != '.').is_err());
+        assert!(map.find_random_tile_loc(&mut thread_rng(), &|c: &Spot<char, char>| c.solid != '.').is_err());
+    }
+
+    #[test]
+    fn test_random_tile_loc_seeded() {
+        let mut map = Map::new("map", 40, 40, &|_| '.');
+        map.add_room(Rectangle::new(0, 0, 10, 10).unwrap());
+        map.add_room(Rectangle::new(20, 20, 15, 15).unwrap());
+        let available = |c: &Spot<char, char>| c.solid == '.';
+
+        let mut rng1 = StdRng::seed_from_u64(7);
+        let mut rng2 = StdRng::seed_from_u64(7);
+        for _ in 0..10 {
+            assert_eq!(map.find_random_tile_loc(&mut rng1, &available),
+                       map.find_random_tile_loc(&mut rng2, &available));
+        }
     }
 
     #[test]
@@ -388,6 +534,30 @@ mod tests {
         assert_eq!(ats, vec![(3, 8), (4, 8), (3, 9)]);
     }
 
+    #[test]
+    fn test_wrap() {
+        let map: Map<char, char> = Map::new("map", 5, 4, &|_| '.').with_wrap(Wrap::HORIZONTAL);
+        let available = |tile: &char| if tile == &'.' { 1 } else { 0 } ;
+
+        assert_eq!(map.offset(&(0, 1), &(-1, 0)), Some((4, 1)));
+        assert_eq!(map.offset(&(4, 1), &(1, 1)), Some((0, 2)));
+        assert_eq!(map.offset(&(0, 0), &(0, -1)), None);
+        assert_eq!(map.delta(&(0, 0), &(4, 3)), (-1, 3));
+        assert_eq!(Wrap::BOTH.delta(&(0, 0), &(4, 3), 5, 4), (-1, -1));
+        assert_eq!(Wrap::BOTH.normalize(-6, 9, 5, 4), Some((4, 1)));
+        assert_eq!(Wrap::BOTH.normalize(0, 0, 0, 4), None);
+        assert_eq!(Wrap::VERTICAL.normalize(0, 0, 5, 0), None);
+
+        //  +--
+        // o|xo
+        // o|oo
+        let ats: Vec<(usize, usize)> = map.adjacent_ats(&(0, 0), &available).map(|(loc, _)| loc).collect();
+        assert_eq!(ats, vec![(4, 0), (1, 0), (4, 1), (0, 1), (1, 1)]);
+        assert_eq!(map.adjacent_paths(&(0, 1), &|c| *c == '.', false), 0b_010_101_010);
+
+        assert_eq!(map.shortest_path(&(0, 2), &(4, 0), &available).unwrap(), (vec![(0, 2), (4, 1), (4, 0)], 2));
+    }
+
     #[test]
     fn test_adjacent_paths() {
         let map_string = "##############\n\
@@ -432,7 +602,7 @@ mod tests {
 
         let mut map: Map<char, char> = generate_ascii_map("map", map_string).unwrap();
         let mut weights = HashMap::new();
-        weights.insert('.', 1 as usize);
+        weights.insert('.', 1_usize);
         let available = |tile: &char| *weights.get(tile).unwrap_or(&0);
         let path = map.shortest_path(&(1, 1), &(12, 1), &available);
         if let Some(path) = path {
@@ -445,20 +615,18 @@ mod tests {
             }
 
             // FIXME: Add weighted test (use 123 as tiles which will just be their weight.
-            // FIXME: WOT!
-            for line in map
-                .iter()
-                .map(|(_, tile)| tile.solid)
-                .collect::<Vec<char>>().chunks(map.width) {
-                for c in line {
-                    print!("{}", *c);
-                }
-                println!("");
-
-            }
+            print!("{}", map);
         }
     }
 
+    #[test]
+    fn test_generate_ascii_map_errors() {
+        assert_eq!(generate_ascii_map("map", "").err(),
+                   Some(Error::Malformed { line: 1, column: 1, message: "empty map".to_string() }));
+        assert_eq!(generate_ascii_map("map", "###\n#.#\n##\n").err(),
+                   Some(Error::Malformed { line: 3, column: 3, message: "row width differs from first row".to_string() }));
+    }
+
     #[test]
     fn test_map_iterator() {
         let map_string = "123\n\
@@ -470,4 +638,42 @@ mod tests {
 
         assert_eq!(string, "123#.####");
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "serde")]
+    fn assert_same_map(a: &Map<char, char>, b: &Map<char, char>) {
+        assert_eq!((&a.name, a.width, a.height, a.wrap), (&b.name, b.width, b.height, b.wrap));
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| a == b));
+        let rooms = |map: &Map<char, char>| map.rooms.iter().map(|r| (r.ulc, r.lrc)).collect::<Vec<_>>();
+        assert_eq!(rooms(a), rooms(b));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut map: Map<char, char> = generate_ascii_map("map", "#####\n#..$#\n#####").unwrap().with_wrap(Wrap::VERTICAL);
+        map.add_room(Rectangle::new(0, 0, 4, 2).unwrap());
+        map.get_mut(&(3, 1)).unwrap().add_item(('$', 20));
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert_same_map(&map, &serde_json::from_str(&json).unwrap());
+
+        let bytes = bincode::serialize(&map).unwrap();
+        assert!(bytes.len() < json.len());
+        assert_same_map(&map, &bincode::deserialize(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_mismatched_size() {
+        let mut map: Map<char, char> = generate_ascii_map("map", "###\n#.#\n###").unwrap();
+        let json = serde_json::to_string(&map).unwrap();
+
+        let error = serde_json::from_str::<Map<char, char>>(&json.replace("\"width\":3", "\"width\":4")).err().unwrap();
+        assert!(error.to_string().starts_with("invalid dimensions: 4x3 map has 3x3 tiles"));
+
+        map.add_room(Rectangle::new(1, 1, 2, 2).unwrap());
+        let json = serde_json::to_string(&map).unwrap();
+        let error = serde_json::from_str::<Map<char, char>>(&json).err().unwrap();
+        assert!(error.to_string().starts_with("(3, 3) is outside of 3x3 map"));
+    }
+}
@@ -0,0 +1,220 @@
+use ndarray::{Array, Axis, Ix2};
+use crate::{Error, Map, Overlay, Spot};
+
+/// What a player remembers of a map.  Every field of view given to update is merged into
+/// explored and a copy of each spot seen is kept, so for fog of war the last seen spot can be
+/// drawn instead of the current one.  Anything out of sight may have changed since.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "MapMemoryData<T, I>"))]
+pub struct MapMemory<T: PartialEq + Clone, I: Default + PartialEq + Clone> {
+    explored: Overlay<bool>,
+    visible: Overlay<bool>,
+    remembered: Array<Option<Spot<T, I>>, Ix2>,
+}
+
+impl<T: PartialEq + Clone, I: Default + PartialEq + Clone> MapMemory<T, I> {
+    pub fn new(map: &Map<T, I>) -> Self {
+        Self {
+            explored: map.create_overlay(),
+            visible: map.create_overlay(),
+            remembered: Array::from_shape_fn((map.width, map.height), |_| None),
+        }
+    }
+
+    /// Merge in light_map (e.g. from calculate_field_of_view) and remember everything in it as
+    /// it is right now.  Whatever was visible before and is not in light_map is now remembered.
+    /// InvalidArgument if light_map is not the size of the map.
+    pub fn update(&mut self, map: &Map<T, I>, light_map: &Overlay<bool>) -> Result<(), Error> {
+        self.explored.or(light_map)?;
+        self.visible.reset();
+        self.visible.or(light_map)?;
+
+        for (loc, seen) in light_map.iter() {
+            if *seen {
+                self.remembered[loc] = map.get(&loc).cloned();
+            }
+        }
+        Ok(())
+    }
+
+    /// Every location ever seen.
+    pub fn explored(&self) -> &Overlay<bool> {
+        &self.explored
+    }
+
+    /// Locations seen by the most recent update.
+    pub fn visible(&self) -> &Overlay<bool> {
+        &self.visible
+    }
+
+    pub fn is_explored(&self, loc: (usize, usize)) -> bool {
+        self.explored.get(loc) == Some(&true)
+    }
+
+    pub fn is_visible(&self, loc: (usize, usize)) -> bool {
+        self.visible.get(loc) == Some(&true)
+    }
+
+    /// What the player believes is at loc.  None if it has never been seen.
+    pub fn remembered(&self, loc: (usize, usize)) -> Option<&Spot<T, I>> {
+        self.remembered.get(loc).and_then(|spot| spot.as_ref())
+    }
+
+    /// Whether what is remembered at loc is no longer what is really there.  Never seen
+    /// locations are not stale since nothing is believed about them.
+    pub fn is_stale(&self, map: &Map<T, I>, loc: (usize, usize)) -> bool {
+        self.remembered(loc).is_some_and(|remembered| map.get(&loc) != Some(remembered))
+    }
+
+    /// All locations where memory and the map disagree.
+    pub fn stale<'a>(&'a self, map: &'a Map<T, I>) -> impl Iterator<Item=(usize, usize)> + 'a {
+        self.explored.iter().filter(move |(loc, _)| self.is_stale(map, *loc)).map(|(loc, _)| loc)
+    }
+
+    pub fn forget(&mut self) {
+        self.explored.reset();
+        self.visible.reset();
+        self.remembered.fill(None);
+    }
+
+    /// Draw the remembered map.  glyph_fn is given each remembered spot and whether it is
+    /// currently visible (so remembered spots can be drawn differently).  Never seen locations
+    /// are drawn as unexplored.
+    pub fn render(&self, glyph_fn: &dyn Fn(&Spot<T, I>, bool) -> char, unexplored: char) -> String {
+        let mut text = String::new();
+
+        for (y, row) in self.remembered.axis_iter(Axis(1)).enumerate() {
+            for (x, spot) in row.iter().enumerate() {
+                text.push(spot.as_ref().map_or(unexplored, |spot| glyph_fn(spot, self.is_visible((x, y)))));
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct MapMemoryData<T: PartialEq + Clone, I: Default + PartialEq + Clone> {
+    explored: Overlay<bool>,
+    visible: Overlay<bool>,
+    remembered: Array<Option<Spot<T, I>>, Ix2>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: PartialEq + Clone, I: Default + PartialEq + Clone> TryFrom<MapMemoryData<T, I>> for MapMemory<T, I> {
+    type Error = Error;
+
+    fn try_from(data: MapMemoryData<T, I>) -> Result<Self, Error> {
+        let (width, height) = data.remembered.dim();
+        for overlay in [&data.explored, &data.visible] {
+            if (overlay.width(), overlay.height()) != (width, height) {
+                return Err(Error::InvalidDimensions(format!("{}x{} memory has a {}x{} overlay", width, height, overlay.width(), overlay.height())))
+            }
+        }
+
+        Ok(MapMemory { explored: data.explored, visible: data.visible, remembered: data.remembered })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::generate_ascii_map;
+    use crate::memory::MapMemory;
+    use crate::{calculate_field_of_view, Spot};
+
+    const MAP: &str = "#########\n\
+                       #...#...#\n\
+                       #.......#\n\
+                       #...#...#\n\
+                       #########\n";
+
+    fn visible(spot: &Spot<char, char>) -> bool {
+        spot.solid != '#'
+    }
+
+    fn glyph(spot: &Spot<char, char>, visible: bool) -> char {
+        if let Some((item, _)) = spot.items.as_ref().and_then(|items| items.first()) {
+            *item
+        } else if visible || spot.solid == '#' {
+            spot.solid
+        } else {
+            ','
+        }
+    }
+
+    #[test]
+    fn test_explore() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let mut memory = MapMemory::new(&map);
+        let mut light_map = map.create_overlay();
+
+        calculate_field_of_view(&map, &(1, 1), 3, &mut light_map, &visible);
+        memory.update(&map, &light_map).unwrap();
+        assert_eq!(memory.render(&glyph, '?'), "####?????\n\
+                                                 #...?????\n\
+                                                 #...?????\n\
+                                                 #...?????\n\
+                                                 ?????????\n");
+
+        calculate_field_of_view(&map, &(6, 2), 3, &mut light_map, &visible);
+        memory.update(&map, &light_map).unwrap();
+        assert!(memory.is_explored((1, 1)) && !memory.is_visible((1, 1)));
+        assert!(memory.is_visible((6, 2)));
+        assert_eq!(memory.render(&glyph, '?'), "#########\n\
+                                                 #,,,#...#\n\
+                                                 #,,,....#\n\
+                                                 #,,,#...#\n\
+                                                 ????#####\n");
+        assert_eq!(format!("{}", memory.explored()), ".........\n\
+                                                      .........\n\
+                                                      .........\n\
+                                                      .........\n\
+                                                      ####.....\n");
+
+        memory.forget();
+        assert!(!memory.is_explored((1, 1)));
+        assert!(memory.remembered((1, 1)).is_none());
+    }
+
+    #[test]
+    fn test_stale() {
+        let mut map = generate_ascii_map("map", MAP).unwrap();
+        let mut memory = MapMemory::new(&map);
+        let mut light_map = map.create_overlay();
+
+        calculate_field_of_view(&map, &(1, 1), 3, &mut light_map, &visible);
+        memory.update(&map, &light_map).unwrap();
+        map.get_mut(&(2, 2)).unwrap().add_item(('$', 5));
+        map.set(&(7, 1), Spot::new('~', None));
+
+        assert!(memory.is_stale(&map, (2, 2)));
+        assert!(!memory.is_stale(&map, (7, 1))); // never seen
+        assert_eq!(memory.remembered((2, 2)).unwrap().items, None);
+        assert_eq!(memory.stale(&map).collect::<Vec<_>>(), vec![(2, 2)]);
+
+        memory.update(&map, &light_map).unwrap();
+        assert_eq!(memory.remembered((2, 2)).unwrap().items, Some(vec![('$', 5)]));
+        assert_eq!(memory.stale(&map).count(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_mismatched_size() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let small = generate_ascii_map("small", "###\n#.#\n###").unwrap();
+        let mut memory = MapMemory::new(&map);
+        let mut light_map = map.create_overlay();
+
+        calculate_field_of_view(&map, &(1, 1), 3, &mut light_map, &visible);
+        memory.update(&map, &light_map).unwrap();
+        let mut json = serde_json::to_value(&memory).unwrap();
+        let back: MapMemory<char, char> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(back.render(&glyph, '?'), memory.render(&glyph, '?'));
+
+        json["remembered"] = serde_json::to_value(MapMemory::new(&small)).unwrap()["remembered"].take();
+        let error = serde_json::from_value::<MapMemory<char, char>>(json).err().unwrap();
+        assert!(error.to_string().starts_with("invalid dimensions: 3x3 memory has a 9x5 overlay"));
+    }
+}
@@ -1,14 +1,17 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::ops::BitOrAssign;
-use ndarray::{Array, Axis, Ix2};
+use std::ops::{AddAssign, BitAndAssign, BitOrAssign, BitXorAssign, Not};
+use ndarray::{Array, Axis, Ix2, Zip};
+use crate::Error;
 
-pub struct Overlay<T: Sized + Clone + BitOrAssign> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+pub struct Overlay<T: Sized + Clone> {
     data: Array<T, Ix2>,
     default: T
 }
 
-impl<T: Sized + Clone + std::ops::BitOrAssign> Overlay<T> {
+impl<T: Sized + Clone> Overlay<T> {
     pub fn new(width: usize, height: usize, default: T) -> Self {
         Overlay {
             data: Array::<T, Ix2>::from_elem((width, height), default.clone()),
@@ -20,12 +23,6 @@ impl<T: Sized + Clone + std::ops::BitOrAssign> Overlay<T> {
         OverlayIterator::new(self)
     }
 
-    pub fn or(&mut self, other: &Self) {
-        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
-            *n |= o.clone();
-        }
-    }
-
     pub fn reset(&mut self) {
         for elem in self.data.iter_mut() {
             *elem = self.default.clone();
@@ -48,15 +45,319 @@ impl<T: Sized + Clone + std::ops::BitOrAssign> Overlay<T> {
 
         found
     }
+
+    pub fn width(&self) -> usize {
+        self.data.len_of(Axis(0))
+    }
+
+    pub fn height(&self) -> usize {
+        self.data.len_of(Axis(1))
+    }
+
+    /// Number of locations passing test.
+    pub fn count(&self, test: &dyn Fn(&T) -> bool) -> usize {
+        self.data.iter().filter(|value| test(value)).count()
+    }
+
+    /// New overlay with f applied to every value (and to the default).
+    pub fn map<U: Sized + Clone>(&self, f: &dyn Fn(&T) -> U) -> Overlay<U> {
+        Overlay {
+            data: self.data.map(f),
+            default: f(&self.default),
+        }
+    }
+
+    /// New overlay combining each location of self and other with f.  InvalidArgument if the two
+    /// are not the same size.
+    pub fn zip_with<U: Sized + Clone, V: Sized + Clone>(&self, other: &Overlay<U>, f: &dyn Fn(&T, &U) -> V) -> Result<Overlay<V>, Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        Ok(Overlay {
+            data: Zip::from(&self.data).and(&other.data).map_collect(f),
+            default: f(&self.default, &other.default),
+        })
+    }
+}
+
+impl<T: Sized + Clone + BitOrAssign> Overlay<T> {
+    /// InvalidArgument if other is not the same size (as with every operation on two overlays).
+    pub fn or(&mut self, other: &Self) -> Result<(), Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
+            *n |= o.clone();
+        }
+        Ok(())
+    }
 }
 
-struct OverlayIterator<'a, T>  where T: Sized + Clone + BitOrAssign {
+impl<T: Sized + Clone + BitAndAssign> Overlay<T> {
+    pub fn and(&mut self, other: &Self) -> Result<(), Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
+            *n &= o.clone();
+        }
+        Ok(())
+    }
+}
+
+impl<T: Sized + Clone + BitXorAssign> Overlay<T> {
+    pub fn xor(&mut self, other: &Self) -> Result<(), Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
+            *n ^= o.clone();
+        }
+        Ok(())
+    }
+}
+
+impl<T: Sized + Clone + Not<Output=T>> Overlay<T> {
+    pub fn not(&mut self) {
+        for n in self.data.iter_mut() {
+            *n = !n.clone();
+        }
+    }
+}
+
+// Numeric overlays such as cost fields.
+impl<T: Sized + Clone + AddAssign> Overlay<T> {
+    pub fn add(&mut self, other: &Self) -> Result<(), Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
+            *n += o.clone();
+        }
+        Ok(())
+    }
+}
+
+impl<T: Sized + Clone + PartialOrd> Overlay<T> {
+    /// Keep the smaller of the two values at each location.
+    pub fn min(&mut self, other: &Self) -> Result<(), Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
+            if *o < *n {
+                *n = o.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the larger of the two values at each location.
+    pub fn max(&mut self, other: &Self) -> Result<(), Error> {
+        same_size(self.data.dim(), other.data.dim())?;
+
+        for (n, o) in self.data.iter_mut().zip(other.data.iter()) {
+            if *o > *n {
+                *n = o.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+const BITS: usize = u64::BITS as usize;
+
+/// Overlay<bool> stored as one bit per location.  Same methods as Overlay<bool> but a
+/// 1000x1000 mask is 125k rather than 1M.  Convert with From when an Overlay<bool> is needed
+/// (e.g. for calculate_field_of_view).
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "BitOverlayData"))]
+#[derive(Clone)]
+pub struct BitOverlay {
+    bits: Vec<u64>,
+    width: usize,
+    height: usize,
+    default: bool,
+}
+
+impl BitOverlay {
+    pub fn new(width: usize, height: usize, default: bool) -> Self {
+        let mut overlay = BitOverlay {
+            bits: vec![0; (width * height).div_ceil(BITS)],
+            width,
+            height,
+            default,
+        };
+        overlay.reset();
+        overlay
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=((usize, usize), &bool)> + '_ {
+        (0..self.width * self.height).map(|index| {
+            let loc = (index % self.width, index / self.width);
+            (loc, self.get(loc).unwrap())
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.bits.fill(if self.default { u64::MAX } else { 0 });
+        self.clear_padding();
+    }
+
+    #[inline]
+    pub fn get(&self, loc: (usize, usize)) -> Option<&bool> {
+        let index = self.index(loc)?;
+
+        Some(if self.bits[index / BITS] & (1 << (index % BITS)) != 0 { &true } else { &false })
+    }
+
+    #[inline]
+    pub fn set(&mut self, loc: (usize, usize), value: bool) -> bool {
+        match self.index(loc) {
+            Some(index) => {
+                if value {
+                    self.bits[index / BITS] |= 1 << (index % BITS);
+                } else {
+                    self.bits[index / BITS] &= !(1 << (index % BITS));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn or(&mut self, other: &Self) -> Result<(), Error> {
+        same_size((self.width, self.height), (other.width, other.height))?;
+
+        for (n, o) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *n |= o;
+        }
+        Ok(())
+    }
+
+    pub fn and(&mut self, other: &Self) -> Result<(), Error> {
+        same_size((self.width, self.height), (other.width, other.height))?;
+
+        for (n, o) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *n &= o;
+        }
+        Ok(())
+    }
+
+    pub fn xor(&mut self, other: &Self) -> Result<(), Error> {
+        same_size((self.width, self.height), (other.width, other.height))?;
+
+        for (n, o) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *n ^= o;
+        }
+        Ok(())
+    }
+
+    pub fn not(&mut self) {
+        for n in self.bits.iter_mut() {
+            *n = !*n;
+        }
+        self.clear_padding();
+    }
+
+    /// Number of locations which are true.
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    fn index(&self, loc: (usize, usize)) -> Option<usize> {
+        if loc.0 < self.width && loc.1 < self.height {
+            Some(loc.1 * self.width + loc.0)
+        } else {
+            None
+        }
+    }
+
+    // Bits past the last location must stay 0 so count is right.
+    fn clear_padding(&mut self) {
+        let used = (self.width * self.height) % BITS;
+        if used != 0 {
+            if let Some(last) = self.bits.last_mut() {
+                *last &= (1 << used) - 1;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct BitOverlayData {
+    bits: Vec<u64>,
+    width: usize,
+    height: usize,
+    default: bool,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BitOverlayData> for BitOverlay {
+    type Error = Error;
+
+    fn try_from(data: BitOverlayData) -> Result<Self, Error> {
+        let expected = data.width.checked_mul(data.height).map(|size| size.div_ceil(BITS));
+        if expected != Some(data.bits.len()) {
+            return Err(Error::InvalidDimensions(format!("{}x{} overlay has {} words of bits", data.width, data.height, data.bits.len())))
+        }
+
+        let mut overlay = BitOverlay { bits: data.bits, width: data.width, height: data.height, default: data.default };
+        overlay.clear_padding();
+        Ok(overlay)
+    }
+}
+
+// Combining overlays only makes sense location by location.
+fn same_size(a: (usize, usize), b: (usize, usize)) -> Result<(), Error> {
+    if a != b {
+        return Err(Error::InvalidArgument(format!("{}x{} overlay combined with {}x{} overlay", a.0, a.1, b.0, b.1)))
+    }
+    Ok(())
+}
+
+impl From<&Overlay<bool>> for BitOverlay {
+    fn from(overlay: &Overlay<bool>) -> Self {
+        let mut bits = BitOverlay::new(overlay.width(), overlay.height(), overlay.default);
+        for (loc, value) in overlay.iter() {
+            bits.set(loc, *value);
+        }
+        bits
+    }
+}
+
+impl From<&BitOverlay> for Overlay<bool> {
+    fn from(bits: &BitOverlay) -> Self {
+        let mut overlay = Overlay::new(bits.width, bits.height, bits.default);
+        for (loc, value) in bits.iter() {
+            overlay.set(loc, *value);
+        }
+        overlay
+    }
+}
+
+impl Display for BitOverlay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for y in 0..self.height {
+            let line: String = (0..self.width)
+                .map(|x| if *self.get((x, y)).unwrap() == self.default { '#' } else { '.' })
+                .collect();
+            let _ = writeln!(f, "{}", line);
+        }
+        Ok(())
+    }
+}
+
+struct OverlayIterator<'a, T>  where T: Sized + Clone {
     overlay: &'a Overlay<T>,
     index: usize,
     width: usize,
 }
 
-impl<'a, T> OverlayIterator<'a, T> where T: Sized + Clone + BitOrAssign {
+impl<'a, T> OverlayIterator<'a, T> where T: Sized + Clone {
     fn new(overlay: &'a Overlay<T>) -> Self {
         let width = overlay.data.len_of(Axis(0));
 
@@ -68,7 +369,7 @@ impl<'a, T> OverlayIterator<'a, T> where T: Sized + Clone + BitOrAssign {
     }
 }
 
-impl<'a, T> Iterator for OverlayIterator<'a, T>  where T: Sized + Clone + BitOrAssign {
+impl<'a, T> Iterator for OverlayIterator<'a, T>  where T: Sized + Clone {
     type Item = ((usize, usize), &'a T);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -84,7 +385,7 @@ impl<'a, T> Iterator for OverlayIterator<'a, T>  where T: Sized + Clone + BitOrA
     }
 }
 
-impl<T: Sized + Clone + BitOrAssign + PartialEq> Display for Overlay<T> {
+impl<T: Sized + Clone + PartialEq> Display for Overlay<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for line in self.data.axis_iter(Axis(1)) {
             let line: String = line.iter().map(|t| if t == &self.default { '#' } else { '.' }).collect();
@@ -96,7 +397,19 @@ impl<T: Sized + Clone + BitOrAssign + PartialEq> Display for Overlay<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::Overlay;
+    use crate::field_of_view::calculate_field_of_view;
+    use crate::map::generate_ascii_map;
+    use crate::{BitOverlay, Error, Overlay};
+
+    fn checkerboard(width: usize, height: usize) -> Overlay<bool> {
+        let mut overlay = Overlay::new(width, height, false);
+        for y in 0..height {
+            for x in 0..width {
+                overlay.set((x, y), (x + y) % 2 == 0);
+            }
+        }
+        overlay
+    }
 
     #[test]
     fn test_or() {
@@ -106,7 +419,7 @@ mod tests {
         o1.set((0, 0), true);
         o2.set((1, 1), true);
 
-        o1.or(&o2);
+        o1.or(&o2).unwrap();
 
         assert!(o1.get((0, 0)).unwrap());
         assert!(o1.get((1, 1)).unwrap());
@@ -128,4 +441,153 @@ mod tests {
         assert_eq!(iter.next(), Some(((0, 1), (&true))));
         assert_eq!(iter.next(), Some(((1, 1), (&false))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_boolean_ops() {
+        let mut o1 = Overlay::new(3, 2, false);
+        let mut o2 = Overlay::new(3, 2, false);
+        o1.set((0, 0), true);
+        o1.set((1, 0), true);
+        o2.set((1, 0), true);
+        o2.set((2, 1), true);
+
+        let mut and = o1.clone();
+        and.and(&o2).unwrap();
+        assert_eq!(format!("{}", and), "#.#\n###\n");
+
+        let mut xor = o1.clone();
+        xor.xor(&o2).unwrap();
+        assert_eq!(format!("{}", xor), ".##\n##.\n");
+
+        xor.not();
+        assert_eq!(format!("{}", xor), "#..\n..#\n");
+        assert_eq!(xor.count(&|value| *value), 4);
+    }
+
+    #[test]
+    fn test_map_and_zip_with() {
+        let mut costs = Overlay::new(2, 2, 1_u32);
+        costs.set((1, 1), 5);
+
+        let doubled = costs.map(&|cost| cost * 2);
+        assert_eq!(doubled.get((1, 1)), Some(&10));
+        assert_eq!(doubled.get((0, 0)), Some(&2));
+
+        let walls = checkerboard(2, 2);
+        let blocked = costs.zip_with(&walls, &|cost, wall| if *wall { u32::MAX } else { *cost }).unwrap();
+        assert_eq!(blocked.iter().map(|(_, cost)| *cost).collect::<Vec<_>>(), vec![u32::MAX, 1, 1, u32::MAX]);
+    }
+
+    #[test]
+    fn test_numeric_ops() {
+        let mut o1 = Overlay::new(2, 1, 1.0_f32);
+        let mut o2 = Overlay::new(2, 1, 2.0_f32);
+        o2.set((1, 0), 0.5);
+
+        let mut sum = o1.clone();
+        sum.add(&o2).unwrap();
+        assert_eq!(sum.iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![3.0, 1.5]);
+
+        let mut min = o1.clone();
+        min.min(&o2).unwrap();
+        assert_eq!(min.iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![1.0, 0.5]);
+
+        o1.max(&o2).unwrap();
+        assert_eq!(o1.iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![2.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bit_overlay() {
+        let bytes = checkerboard(13, 7);
+        let mut bits = BitOverlay::from(&bytes);
+
+        assert_eq!(format!("{}", bits), format!("{}", bytes));
+        assert_eq!(bits.count(), bytes.count(&|value| *value));
+        assert_eq!(bits.get((13, 0)), None);
+        assert!(!bits.set((0, 7), true));
+
+        bits.not();
+        assert_eq!(bits.count(), 13 * 7 - bytes.count(&|value| *value));
+
+        let mut other = BitOverlay::new(13, 7, true);
+        other.set((1, 0), false);
+        bits.and(&other).unwrap();
+        assert!(!*bits.get((1, 0)).unwrap());
+        assert!(*bits.get((3, 0)).unwrap());
+
+        bits.or(&other).unwrap();
+        bits.xor(&other).unwrap();
+        let back = Overlay::from(&bits);
+        assert_eq!(back.iter().collect::<Vec<_>>(), bits.iter().collect::<Vec<_>>());
+        assert_eq!(back.count(&|value| *value), 0);
+    }
+
+    #[test]
+    fn test_mismatched_sizes() {
+        let mut o1 = Overlay::new(3, 4, 1);
+        let o2 = Overlay::new(4, 3, 1);
+        let error = Err(Error::InvalidArgument("3x4 overlay combined with 4x3 overlay".to_string()));
+
+        assert_eq!(o1.add(&o2), error);
+        assert_eq!(o1.max(&o2), error);
+        assert!(o1.zip_with(&o2, &|a, b| a + b).is_err());
+        assert_eq!(o1.count(&|value| *value == 1), 12);
+
+        let mut bits = BitOverlay::new(3, 4, false);
+        assert_eq!(bits.or(&BitOverlay::new(4, 3, true)), error);
+        assert_eq!(bits.count(), 0);
+    }
+
+    #[test]
+    fn test_bit_overlay_field_of_view() {
+        let map = generate_ascii_map("map", "#####\n#..##\n#####").unwrap();
+        let mut seen = Overlay::new(map.width, map.height, false);
+        calculate_field_of_view(&map, &(1, 1), 3, &mut seen, &|spot| spot.solid == '.');
+
+        // Keep what was seen as bits and hand it back to field of view when needed again.
+        let mut memory = BitOverlay::from(&seen);
+        assert_eq!(memory.count(), seen.count(&|value| *value));
+
+        calculate_field_of_view(&map, &(2, 1), 3, &mut seen, &|spot| spot.solid == '.');
+        memory.or(&BitOverlay::from(&seen)).unwrap();
+        assert_eq!(format!("{}", Overlay::from(&memory)), format!("{}", memory));
+    }
+
+    #[test]
+    fn test_bit_overlay_size() {
+        let bits = BitOverlay::new(1000, 1000, true);
+
+        assert_eq!(bits.bits.len(), 15625);
+        assert_eq!(bits.count(), 1_000_000);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut o1 = Overlay::new(3, 4, false);
+        o1.set((2, 3), true);
+
+        let json: Overlay<bool> = serde_json::from_str(&serde_json::to_string(&o1).unwrap()).unwrap();
+        assert_eq!(json.iter().collect::<Vec<_>>(), o1.iter().collect::<Vec<_>>());
+
+        let binary: Overlay<bool> = bincode::deserialize(&bincode::serialize(&o1).unwrap()).unwrap();
+        assert_eq!(binary.iter().collect::<Vec<_>>(), o1.iter().collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_mismatched_size() {
+        // The size of an Overlay is the size of its array which already has to match its data.
+        let json = serde_json::to_string(&Overlay::new(3, 4, false)).unwrap();
+        assert!(serde_json::from_str::<Overlay<bool>>(&json.replace("[3,4]", "[4,4]")).is_err());
+
+        let mut bits = BitOverlay::new(10, 10, false);
+        bits.set((9, 9), true);
+        let json = serde_json::to_string(&bits).unwrap();
+        let back: BitOverlay = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.iter().collect::<Vec<_>>(), bits.iter().collect::<Vec<_>>());
+
+        let error = serde_json::from_str::<BitOverlay>(&json.replace("\"height\":10", "\"height\":100")).err().unwrap();
+        assert!(error.to_string().starts_with("invalid dimensions: 10x100 overlay has 2 words of bits"));
+    }
+}
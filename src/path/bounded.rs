@@ -0,0 +1,199 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use crate::{Map, Movement, Overlay};
+
+/// How much work a bounded search may do before giving up.  None means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SearchLimit {
+    /// Paths costing more than this are never considered.
+    pub max_cost: Option<usize>,
+    /// Most locations which will be expanded.
+    pub max_nodes: Option<usize>,
+}
+
+impl SearchLimit {
+    pub fn cost(max_cost: usize) -> Self {
+        Self { max_cost: Some(max_cost), max_nodes: None }
+    }
+
+    pub fn nodes(max_nodes: usize) -> Self {
+        Self { max_cost: None, max_nodes: Some(max_nodes) }
+    }
+}
+
+/// Result of Map::closest_path.  When complete is false path leads to the location closest to
+/// the goal which could be reached within the limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialPath {
+    pub path: Vec<(usize, usize)>,
+    pub cost: usize,
+    pub complete: bool,
+}
+
+// Each location reached with its cheapest known cost and where it was reached from.
+type Visited = HashMap<(usize, usize), (usize, (usize, usize))>;
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// A* which gives up once limit is hit.  None if end cannot be reached within limit.
+    pub fn bounded_path(&self, movement: &Movement, start: &(usize, usize), end: &(usize, usize),
+                        limit: &SearchLimit, available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
+        let partial = self.closest_path(movement, start, end, limit, available);
+
+        partial.complete.then_some((partial.path, partial.cost))
+    }
+
+    /// A* which never fails.  If end is unreachable (or not reachable within limit) the path
+    /// to the location closest to end by movement's heuristic is returned instead with the
+    /// cheapest of those winning ties.
+    pub fn closest_path(&self, movement: &Movement, start: &(usize, usize), end: &(usize, usize),
+                        limit: &SearchLimit, available: &dyn Fn(&T) -> usize) -> PartialPath {
+        let mut visited: Visited = HashMap::from([(*start, (0, *start))]);
+        let mut open = BinaryHeap::from([Reverse((movement.estimate_on(self, start, end), 0, *start))]);
+        let mut closest = (movement.estimate_on(self, start, end), 0, *start);
+        let mut expanded = 0;
+
+        while let Some(Reverse((_, cost, loc))) = open.pop() {
+            if cost > visited[&loc].0 { // Cheaper way here already expanded
+                continue
+            }
+
+            if loc == *end {
+                return PartialPath { path: rebuild(&visited, loc), cost, complete: true }
+            }
+
+            if limit.max_nodes.is_some_and(|max_nodes| expanded >= max_nodes) {
+                break
+            }
+            expanded += 1;
+
+            let estimate = movement.estimate_on(self, &loc, end);
+            if (estimate, cost) < (closest.0, closest.1) {
+                closest = (estimate, cost, loc);
+            }
+
+            for (next, step) in movement.neighbors(self, &loc, available) {
+                let next_cost = cost + step;
+                if limit.max_cost.is_some_and(|max_cost| next_cost > max_cost) {
+                    continue
+                }
+
+                if visited.get(&next).is_none_or(|(known, _)| next_cost < *known) {
+                    visited.insert(next, (next_cost, loc));
+                    open.push(Reverse((next_cost + movement.estimate_on(self, &next, end), next_cost, next)));
+                }
+            }
+        }
+
+        PartialPath { path: rebuild(&visited, closest.2), cost: closest.1, complete: false }
+    }
+
+    /// Every location which can be reached from start for at most max_cost (start included).
+    /// Handy for showing how far a unit can move this turn.
+    pub fn reachable_within(&self, movement: &Movement, start: &(usize, usize), max_cost: usize,
+                            available: &dyn Fn(&T) -> usize) -> Overlay<bool> {
+        let mut reachable = self.create_overlay();
+        let mut costs = HashMap::from([(*start, 0)]);
+        let mut open = BinaryHeap::from([Reverse((0, *start))]);
+
+        while let Some(Reverse((cost, loc))) = open.pop() {
+            if cost > costs[&loc] {
+                continue
+            }
+            reachable.set(loc, true);
+
+            for (next, step) in movement.neighbors(self, &loc, available) {
+                let next_cost = cost + step;
+                if next_cost <= max_cost && costs.get(&next).is_none_or(|known| next_cost < *known) {
+                    costs.insert(next, next_cost);
+                    open.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        reachable
+    }
+}
+
+fn rebuild(visited: &Visited, end: (usize, usize)) -> Vec<(usize, usize)> {
+    let mut path = vec![end];
+    let mut loc = end;
+
+    while let Some((_, previous)) = visited.get(&loc).filter(|(_, previous)| *previous != loc) {
+        path.push(*previous);
+        loc = *previous;
+    }
+
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::generate_ascii_map;
+    use crate::{Movement, SearchLimit};
+
+    const MAP: &str = "......#...\n\
+                       ......#...\n\
+                       ......#.$.\n\
+                       ......#...\n";
+
+    fn available(tile: &char) -> usize {
+        match tile {
+            '.' => 1,
+            '~' => 3,
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_bounded_path() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+
+        let (path, cost) = map.bounded_path(&Movement::EIGHT_WAY, &(0, 0), &(5, 3), &SearchLimit::cost(5), &available).unwrap();
+        assert_eq!((path.len(), cost), (6, 5));
+        assert_eq!(map.bounded_path(&Movement::EIGHT_WAY, &(0, 0), &(5, 3), &SearchLimit::cost(4), &available), None);
+        assert_eq!(map.bounded_path(&Movement::EIGHT_WAY, &(0, 0), &(5, 3), &SearchLimit::nodes(2), &available), None);
+        assert!(map.bounded_path(&Movement::EIGHT_WAY, &(0, 0), &(5, 3), &SearchLimit::default(), &available).is_some());
+    }
+
+    #[test]
+    fn test_closest_path() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+
+        // Wall splits the map so the best is to walk up to it.
+        let partial = map.closest_path(&Movement::FOUR_WAY, &(0, 2), &(8, 2), &SearchLimit::default(), &available);
+        assert!(!partial.complete);
+        assert_eq!((partial.path.last(), partial.cost), (Some(&(5, 2)), 5));
+
+        let partial = map.closest_path(&Movement::FOUR_WAY, &(0, 2), &(8, 2), &SearchLimit::cost(3), &available);
+        assert_eq!(partial.path, vec![(0, 2), (1, 2), (2, 2), (3, 2)]);
+
+        let partial = map.closest_path(&Movement::EIGHT_WAY, &(7, 0), &(8, 2), &SearchLimit::nodes(1), &available);
+        assert_eq!(partial.path, vec![(7, 0)]);
+
+        let partial = map.closest_path(&Movement::EIGHT_WAY, &(7, 0), &(8, 3), &SearchLimit::default(), &available);
+        assert!(partial.complete);
+        assert_eq!(partial.cost, 3);
+
+        // A goal which can never be entered still gets us next to it.
+        let partial = map.closest_path(&Movement::EIGHT_WAY, &(7, 0), &(8, 2), &SearchLimit::default(), &available);
+        assert!(!partial.complete);
+        assert_eq!(partial.cost, 1);
+    }
+
+    #[test]
+    fn test_reachable_within() {
+        let mut map = generate_ascii_map("map", MAP).unwrap();
+        map.set(&(1, 1), crate::Spot::new('~', None));
+
+        let reachable = map.reachable_within(&Movement::FOUR_WAY, &(0, 0), 2, &available);
+        assert_eq!(format!("{}", reachable), "...#######\n\
+                                              .#########\n\
+                                              .#########\n\
+                                              ##########\n");
+
+        let reachable = map.reachable_within(&Movement::EIGHT_WAY, &(5, 0), 3, &available);
+        assert_eq!(reachable.count(&|seen| *seen), 16);
+        assert_eq!(reachable.get((7, 0)), Some(&false));
+    }
+}
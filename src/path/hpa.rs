@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+use pathfinding::prelude::{astar, dijkstra_all};
+use crate::{Error, Map, Movement, Wrap};
+
+// Shortest run of open tiles along a border which gets an entrance at each end instead of one
+// in the middle.
+const LONG_ENTRANCE: usize = 6;
+
+// Location reached and the cost to get there.
+type Edge = ((usize, usize), usize);
+// Pair of locations either side of a border.
+type Transition = ((usize, usize), (usize, usize));
+
+// Border on the right (Vertical) or bottom (Horizontal) of the cluster at (column, row).
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Border {
+    Vertical(usize, usize),
+    Horizontal(usize, usize),
+}
+
+struct Cluster {
+    ulc: (usize, usize),
+    lrc: (usize, usize),
+    entrances: Vec<(usize, usize)>,
+    // Cheapest cost from each entrance to the others without leaving the cluster.
+    costs: HashMap<(usize, usize), Vec<Edge>>,
+}
+
+impl Cluster {
+    fn contains(&self, loc: &(usize, usize)) -> bool {
+        loc.0 >= self.ulc.0 && loc.0 <= self.lrc.0 && loc.1 >= self.ulc.1 && loc.1 <= self.lrc.1
+    }
+}
+
+/// Hierarchical pathfinding (HPA*) for long trips across big maps.  The map is cut into square
+/// clusters and the cost between every entrance of a cluster is worked out up front, so a search
+/// only crosses a small graph of entrances and then fills in the tiles along the way.  Paths are
+/// close to but not always as cheap as shortest_path.
+///
+/// available must be the same for construction, invalidate and shortest_path.  After changing a
+/// tile with Map::set call invalidate with its location.  Maps which wrap are not supported.
+///
+/// <https://webdocs.cs.ualberta.ca/~mmueller/ps/hpastar.pdf>
+pub struct HierarchicalMap {
+    cluster_size: usize,
+    columns: usize,
+    rows: usize,
+    clusters: Vec<Cluster>,
+    borders: HashMap<Border, Vec<Transition>>,
+    // Both directions of every pair in borders.
+    transitions: HashMap<(usize, usize), Vec<(usize, usize)>>,
+}
+
+impl HierarchicalMap {
+    pub fn new<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, cluster_size: usize,
+                                                    available: &dyn Fn(&T) -> usize) -> Result<Self, Error> {
+        if cluster_size < 2 {
+            return Err(Error::InvalidArgument("cluster_size must be at least 2".to_string()))
+        }
+
+        if map.wrap != Wrap::NONE {
+            return Err(Error::InvalidArgument("wrapping maps are not supported".to_string()))
+        }
+
+        let columns = map.width.div_ceil(cluster_size);
+        let rows = map.height.div_ceil(cluster_size);
+        let clusters = (0..rows * columns).map(|index| {
+            let ulc = ((index % columns) * cluster_size, (index / columns) * cluster_size);
+            let lrc = ((ulc.0 + cluster_size).min(map.width) - 1, (ulc.1 + cluster_size).min(map.height) - 1);
+
+            Cluster { ulc, lrc, entrances: vec![], costs: HashMap::new() }
+        }).collect();
+
+        let mut hierarchy = Self {
+            cluster_size,
+            columns,
+            rows,
+            clusters,
+            borders: HashMap::new(),
+            transitions: HashMap::new(),
+        };
+
+        for row in 0..rows {
+            for column in 0..columns {
+                hierarchy.build_borders(map, (column, row), available);
+            }
+        }
+        for index in 0..hierarchy.clusters.len() {
+            hierarchy.build_cluster(map, index, available);
+        }
+
+        Ok(hierarchy)
+    }
+
+    pub fn cluster_count(&self) -> usize {
+        self.clusters.len()
+    }
+
+    /// Number of entrances across all clusters (nodes in the abstract graph).
+    pub fn entrance_count(&self) -> usize {
+        self.clusters.iter().map(|cluster| cluster.entrances.len()).sum()
+    }
+
+    /// Rebuild the cluster holding loc (and the entrances it shares with its neighbours) after
+    /// the tile at loc changed.
+    pub fn invalidate<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, loc: &(usize, usize),
+                                                           available: &dyn Fn(&T) -> usize) {
+        if !map.is_valid_loc(loc) {
+            return
+        }
+
+        let neighbours = self.around((loc.0 / self.cluster_size, loc.1 / self.cluster_size));
+
+        // Borders of neighbours may have diagonal entrances touching this cluster's corners.
+        for cluster in neighbours.iter() {
+            self.build_borders(map, *cluster, available);
+        }
+        for (column, row) in neighbours {
+            self.build_cluster(map, row * self.columns + column, available);
+        }
+    }
+
+    /// Path from start to end (both included) and its cost.  None if end cannot be reached.
+    pub fn shortest_path<T: PartialEq, I: Default + PartialEq>(&self, map: &Map<T, I>, start: &(usize, usize),
+                                                              end: &(usize, usize),
+                                                              available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
+        if !map.is_valid_loc(start) || !map.is_valid_loc(end) {
+            return None
+        }
+
+        if start == end {
+            return Some((vec![*start], 0))
+        }
+
+        // Temporarily join start and end to the entrances of their clusters.
+        let start_cluster = &self.clusters[self.cluster_of(start)];
+        let end_cluster = &self.clusters[self.cluster_of(end)];
+        let from_start = within(map, start_cluster, start, available, false);
+        let to_end = within(map, end_cluster, end, available, true);
+
+        let mut start_edges: Vec<Edge> = start_cluster.entrances.iter()
+            .filter_map(|entrance| from_start.get(entrance).map(|(_, cost)| (*entrance, *cost)))
+            .collect();
+        if let Some((_, cost)) = from_start.get(end) {
+            start_edges.push((*end, *cost));
+        }
+
+        let successors = |loc: &(usize, usize)| {
+            let mut next = vec![];
+
+            if loc == start {
+                next.extend(start_edges.iter().copied());
+            }
+
+            if let Some(costs) = self.clusters[self.cluster_of(loc)].costs.get(loc) {
+                next.extend(costs.iter().copied());
+            }
+
+            for other in self.transitions.get(loc).into_iter().flatten() {
+                next.push((*other, available(&map.get(other).unwrap().solid)));
+            }
+
+            if end_cluster.contains(loc) {
+                if let Some((_, cost)) = to_end.get(loc) {
+                    next.push((*end, *cost));
+                }
+            }
+
+            next
+        };
+        let (nodes, cost) = astar(start, successors, |loc| Movement::EIGHT_WAY.estimate(loc, end), |loc| loc == end)?;
+
+        let mut path = vec![*start];
+        for pair in nodes.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+
+            if self.cluster_of(&from) != self.cluster_of(&to) {
+                path.push(to);
+            } else {
+                let (steps, _) = self.refine(map, &from, &to, available)?;
+                path.extend(steps.into_iter().skip(1));
+            }
+        }
+
+        Some((path, cost))
+    }
+
+    fn cluster_of(&self, loc: &(usize, usize)) -> usize {
+        (loc.1 / self.cluster_size) * self.columns + loc.0 / self.cluster_size
+    }
+
+    // Cluster at (column, row) and every cluster touching it (including at a corner).
+    fn around(&self, (column, row): (usize, usize)) -> Vec<(usize, usize)> {
+        (row.saturating_sub(1)..=(row + 1).min(self.rows - 1))
+            .flat_map(|row| (column.saturating_sub(1)..=(column + 1).min(self.columns - 1)).map(move |column| (column, row)))
+            .collect()
+    }
+
+    // Tile path between two locations in the same cluster without leaving it.
+    fn refine<T: PartialEq, I: Default + PartialEq>(&self, map: &Map<T, I>, from: &(usize, usize), to: &(usize, usize),
+                                                   available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
+        let cluster = &self.clusters[self.cluster_of(from)];
+
+        astar(from,
+              |loc| map.adjacent_ats(loc, available).filter(|(next, _)| cluster.contains(next)).collect::<Vec<_>>(),
+              |loc| Movement::EIGHT_WAY.estimate(loc, to),
+              |loc| loc == to)
+    }
+
+    fn build_borders<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, (column, row): (usize, usize),
+                                                          available: &dyn Fn(&T) -> usize) {
+        let cluster = &self.clusters[row * self.columns + column];
+        let (ulc, lrc) = (cluster.ulc, cluster.lrc);
+        let open = |loc: (usize, usize)| map.get(&loc).is_some_and(|spot| available(&spot.solid) != 0);
+
+        if column + 1 < self.columns {
+            let x = lrc.0;
+            let straight = |y: usize| open((x, y)) && open((x + 1, y));
+            let mut pairs = entrances(ulc.1..=lrc.1, &straight, &|y| ((x, y), (x + 1, y)));
+
+            // Diagonal squeezes only matter where nothing straight across is nearby.
+            for y in ulc.1..=lrc.1 {
+                for other in [y.wrapping_sub(1), y + 1] {
+                    if other < map.height && open((x, y)) && open((x + 1, other)) && !straight(y) && !straight(other) {
+                        pairs.push(((x, y), (x + 1, other)));
+                    }
+                }
+            }
+            self.set_border(Border::Vertical(column, row), pairs);
+        }
+
+        if row + 1 < self.rows {
+            let y = lrc.1;
+            let straight = |x: usize| open((x, y)) && open((x, y + 1));
+            let mut pairs = entrances(ulc.0..=lrc.0, &straight, &|x| ((x, y), (x, y + 1)));
+
+            // Diagonals across a cluster corner are already found by the vertical border.
+            for x in ulc.0..=lrc.0 {
+                for other in [x.wrapping_sub(1), x + 1] {
+                    if other >= ulc.0 && other <= lrc.0 && open((x, y)) && open((other, y + 1)) && !straight(x) && !straight(other) {
+                        pairs.push(((x, y), (other, y + 1)));
+                    }
+                }
+            }
+            self.set_border(Border::Horizontal(column, row), pairs);
+        }
+    }
+
+    // Replace the pairs across border updating transitions for only those pairs.
+    fn set_border(&mut self, border: Border, pairs: Vec<Transition>) {
+        for (a, b) in self.borders.remove(&border).into_iter().flatten() {
+            for (from, to) in [(a, b), (b, a)] {
+                if let Some(others) = self.transitions.get_mut(&from) {
+                    others.retain(|other| *other != to);
+                    if others.is_empty() {
+                        self.transitions.remove(&from);
+                    }
+                }
+            }
+        }
+
+        for (a, b) in pairs.iter() {
+            self.transitions.entry(*a).or_default().push(*b);
+            self.transitions.entry(*b).or_default().push(*a);
+        }
+        self.borders.insert(border, pairs);
+    }
+
+    fn build_cluster<T: PartialEq, I: Default + PartialEq>(&mut self, map: &Map<T, I>, index: usize,
+                                                          available: &dyn Fn(&T) -> usize) {
+        let cluster = &self.clusters[index];
+        // Only borders of this cluster and the ones around it can have an end inside it.
+        let mut entrances: Vec<(usize, usize)> = self.around((index % self.columns, index / self.columns)).into_iter()
+            .flat_map(|(column, row)| [Border::Vertical(column, row), Border::Horizontal(column, row)])
+            .filter_map(|border| self.borders.get(&border))
+            .flatten()
+            .flat_map(|(a, b)| [*a, *b])
+            .filter(|loc| cluster.contains(loc))
+            .collect();
+        entrances.sort();
+        entrances.dedup();
+
+        let mut costs = HashMap::new();
+        for entrance in entrances.iter() {
+            let reached = within(map, cluster, entrance, available, false);
+            let edges: Vec<Edge> = entrances.iter()
+                .filter_map(|other| reached.get(other).map(|(_, cost)| (*other, *cost)))
+                .collect();
+            costs.insert(*entrance, edges);
+        }
+
+        let cluster = &mut self.clusters[index];
+        cluster.entrances = entrances;
+        cluster.costs = costs;
+    }
+}
+
+// Cost from loc to everything reachable without leaving cluster.  When reverse is true it is the
+// cost from everything to loc instead (entering a tile is what costs so the two differ).
+fn within<T: PartialEq, I: Default + PartialEq>(map: &Map<T, I>, cluster: &Cluster, loc: &(usize, usize),
+                                               available: &dyn Fn(&T) -> usize,
+                                               reverse: bool) -> HashMap<(usize, usize), ((usize, usize), usize)> {
+    let mut reached = dijkstra_all(loc, |current| {
+        let enter = available(&map.get(current).unwrap().solid);
+
+        map.adjacent_ats(current, available)
+            .filter(|(next, _)| cluster.contains(next))
+            .map(|(next, cost)| (next, if reverse { enter } else { cost }))
+            .collect::<Vec<_>>()
+    });
+    reached.insert(*loc, (*loc, 0));
+    reached
+}
+
+// Entrance pairs for each run of locations where straight says the border can be crossed.
+fn entrances(range: std::ops::RangeInclusive<usize>, straight: &dyn Fn(usize) -> bool,
+             pair: &dyn Fn(usize) -> Transition) -> Vec<Transition> {
+    let mut pairs = vec![];
+    let mut run_start = None;
+
+    for i in range.clone().chain(std::iter::once(range.end() + 1)) {
+        let open = i <= *range.end() && straight(i);
+
+        match (run_start, open) {
+            (None, true) => run_start = Some(i),
+            (Some(first), false) => {
+                let last = i - 1;
+                if last - first + 1 >= LONG_ENTRANCE {
+                    pairs.push(pair(first));
+                    pairs.push(pair(last));
+                } else {
+                    pairs.push(pair((first + last) / 2));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::map::generate_ascii_map;
+    use crate::path::hpa::HierarchicalMap;
+    use crate::{DijkstraMap, Map, Spot};
+
+    fn available(tile: &char) -> usize {
+        match tile {
+            '.' => 1,
+            '~' => 4,
+            _ => 0,
+        }
+    }
+
+    // Checks each step is a legal move and that the steps add up to cost.
+    fn assert_valid(map: &Map<char, char>, path: &[(usize, usize)], cost: usize) {
+        let mut total = 0;
+        for pair in path.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert!(a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1 && a != b, "{:?} -> {:?}", a, b);
+            total += available(&map.get(&b).unwrap().solid);
+        }
+        assert_eq!(total, cost);
+    }
+
+    #[test]
+    fn test_invalid_cluster_size() {
+        let map: Map<char, char> = Map::new("map", 10, 10, &|_| '.');
+
+        assert!(HierarchicalMap::new(&map, 1, &available).is_err());
+    }
+
+    #[test]
+    fn test_open_map() {
+        let map: Map<char, char> = Map::new("map", 30, 20, &|_| '.');
+        let hierarchy = HierarchicalMap::new(&map, 8, &available).unwrap();
+        let (path, cost) = hierarchy.shortest_path(&map, &(0, 0), &(29, 19), &available).unwrap();
+
+        assert_eq!(hierarchy.cluster_count(), 12);
+        assert_eq!((path[0], *path.last().unwrap()), ((0, 0), (29, 19)));
+        assert_valid(&map, &path, cost);
+        // 29 is optimal but crossing at entrances can add a small detour.
+        assert!((29..=32).contains(&cost), "{}", cost);
+        assert_eq!(hierarchy.shortest_path(&map, &(3, 3), &(5, 6), &available).unwrap().1, 3);
+    }
+
+    #[test]
+    fn test_close_to_optimal() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..10 {
+            let mut map: Map<char, char> = Map::new("map", 40, 40, &|_| '.');
+            for _ in 0..300 {
+                map.set(&(rng.gen_range(0..40), rng.gen_range(0..40)), Spot::new('#', None));
+            }
+            for _ in 0..100 {
+                map.set(&(rng.gen_range(0..40), rng.gen_range(0..40)), Spot::new('~', None));
+            }
+            let (start, end) = ((rng.gen_range(0..40), rng.gen_range(0..40)), (rng.gen_range(0..40), rng.gen_range(0..40)));
+            map.set(&start, Spot::new('.', None));
+            map.set(&end, Spot::new('.', None));
+
+            let hierarchy = HierarchicalMap::new(&map, 10, &available).unwrap();
+            let optimal = DijkstraMap::new(&map, &[end], &available).get(start).unwrap();
+            match hierarchy.shortest_path(&map, &start, &end, &available) {
+                Some((path, cost)) => {
+                    assert_valid(&map, &path, cost);
+                    assert!(cost as f32 >= optimal);
+                    assert!(cost as f32 <= optimal * 1.5, "{} vs {}", cost, optimal);
+                }
+                None => assert!(!optimal.is_finite()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut map = generate_ascii_map("map", "....#....\n\
+                                                  ....#....\n\
+                                                  ....#....\n\
+                                                  .........\n\
+                                                  ....#....\n").unwrap();
+        let mut hierarchy = HierarchicalMap::new(&map, 3, &available).unwrap();
+        let (path, cost) = hierarchy.shortest_path(&map, &(0, 0), &(8, 0), &available).unwrap();
+        assert_valid(&map, &path, cost);
+        assert!(path.contains(&(4, 3)));
+
+        map.set(&(4, 3), Spot::new('#', None));
+        hierarchy.invalidate(&map, &(4, 3), &available);
+        assert!(hierarchy.shortest_path(&map, &(0, 0), &(8, 0), &available).is_none());
+
+        map.set(&(4, 0), Spot::new('~', None));
+        hierarchy.invalidate(&map, &(4, 0), &available);
+        let (path, cost) = hierarchy.shortest_path(&map, &(0, 0), &(8, 0), &available).unwrap();
+        assert_valid(&map, &path, cost);
+        assert_eq!(cost, 11);
+    }
+
+    #[test]
+    fn test_invalidate_matches_rebuild() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut map: Map<char, char> = Map::new("map", 23, 17, &|_| '.');
+        let mut hierarchy = HierarchicalMap::new(&map, 5, &available).unwrap();
+
+        for _ in 0..60 {
+            let loc = (rng.gen_range(0..23), rng.gen_range(0..17));
+            map.set(&loc, Spot::new(['.', '#', '~'][rng.gen_range(0..3)], None));
+            hierarchy.invalidate(&map, &loc, &available);
+
+            let fresh = HierarchicalMap::new(&map, 5, &available).unwrap();
+            let transitions = |hierarchy: &HierarchicalMap| {
+                let mut pairs: Vec<_> = hierarchy.transitions.iter()
+                    .flat_map(|(a, others)| others.iter().map(move |b| (*a, *b)))
+                    .collect();
+                pairs.sort();
+                pairs
+            };
+            assert_eq!(transitions(&hierarchy), transitions(&fresh));
+            for (cluster, expected) in hierarchy.clusters.iter().zip(fresh.clusters.iter()) {
+                assert_eq!(cluster.entrances, expected.entrances);
+                assert_eq!(cluster.costs, expected.costs);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use pathfinding::prelude::astar;
+use crate::{add_delta, Map, Movement, Wrap};
+
+const DIRECTIONS: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+// Location along with the direction we jumped to reach it ((0, 0) for start).
+type Node = ((usize, usize), (isize, isize));
+
+// https://harablog.wordpress.com/2011/09/07/jump-point-search/
+//
+// Moves are 8 way with every step costing 1 which is the same as shortest_path with uniform
+// costs.  Diagonals may squeeze between two blocked tiles just like adjacent_ats allows.
+struct JumpPointSearch<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a Map<T, I>,
+    passable: &'a dyn Fn(&T) -> bool,
+    end: (usize, usize),
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> JumpPointSearch<'a, T, I> {
+    fn is_open(&self, loc: (usize, usize), delta: (isize, isize)) -> bool {
+        add_delta(&loc, &delta)
+            .and_then(|loc| self.map.get(&loc))
+            .is_some_and(|spot| (self.passable)(&spot.solid))
+    }
+
+    // Directions worth jumping in from loc after arriving by moving in direction.
+    fn directions(&self, loc: (usize, usize), direction: (isize, isize)) -> Vec<(isize, isize)> {
+        let (dx, dy) = direction;
+
+        if direction == (0, 0) {
+            return DIRECTIONS.to_vec()
+        }
+
+        let mut directions = vec![direction];
+        if dx != 0 && dy != 0 {
+            directions.push((dx, 0));
+            directions.push((0, dy));
+            if !self.is_open(loc, (-dx, 0)) {
+                directions.push((-dx, dy));
+            }
+            if !self.is_open(loc, (0, -dy)) {
+                directions.push((dx, -dy));
+            }
+        } else if dx != 0 {
+            for side in [-1, 1] {
+                if !self.is_open(loc, (0, side)) {
+                    directions.push((dx, side));
+                }
+            }
+        } else {
+            for side in [-1, 1] {
+                if !self.is_open(loc, (side, 0)) {
+                    directions.push((side, dy));
+                }
+            }
+        }
+
+        directions
+    }
+
+    // Whether something we cannot see past from the side would force a turn at loc.
+    fn has_forced_neighbour(&self, loc: (usize, usize), direction: (isize, isize)) -> bool {
+        let (dx, dy) = direction;
+
+        if dx != 0 && dy != 0 {
+            (!self.is_open(loc, (-dx, 0)) && self.is_open(loc, (-dx, dy))) ||
+                (!self.is_open(loc, (0, -dy)) && self.is_open(loc, (dx, -dy)))
+        } else if dx != 0 {
+            [-1, 1].iter().any(|side| !self.is_open(loc, (0, *side)) && self.is_open(loc, (dx, *side)))
+        } else {
+            [-1, 1].iter().any(|side| !self.is_open(loc, (*side, 0)) && self.is_open(loc, (*side, dy)))
+        }
+    }
+
+    // Move from loc in direction until reaching the end, a forced neighbour or a dead end.
+    fn jump(&self, mut loc: (usize, usize), direction: (isize, isize)) -> Option<(usize, usize)> {
+        let (dx, dy) = direction;
+
+        loop {
+            if !self.is_open(loc, direction) {
+                return None
+            }
+            loc = add_delta(&loc, &direction).unwrap();
+
+            if loc == self.end || self.has_forced_neighbour(loc, direction) {
+                return Some(loc)
+            }
+
+            // Diagonals stop wherever a straight jump would find something.
+            if dx != 0 && dy != 0 && (self.jump(loc, (dx, 0)).is_some() || self.jump(loc, (0, dy)).is_some()) {
+                return Some(loc)
+            }
+        }
+    }
+
+    fn successors(&self, node: &Node) -> Vec<(Node, usize)> {
+        let (loc, direction) = *node;
+
+        self.directions(loc, direction).into_iter()
+            .filter_map(|direction| self.jump(loc, direction).map(|next| ((next, direction), Movement::EIGHT_WAY.estimate(&loc, &next))))
+            .collect()
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// Jump Point Search for maps where every passable tile costs the same.  Returns the same
+    /// path (every location from start to end) and cost as shortest_path would with a cost of 1
+    /// for each passable tile, but skips over most of the tiles in open areas.  Jumps would never
+    /// end on a wrapping map so those just use shortest_path.
+    pub fn jump_point_search(&self, start: &(usize, usize), end: &(usize, usize),
+                             passable: &dyn Fn(&T) -> bool) -> Option<(Vec<(usize, usize)>, usize)> {
+        if !self.is_valid_loc(start) || !self.is_valid_loc(end) {
+            return None
+        }
+
+        if self.wrap != Wrap::NONE {
+            return self.shortest_path(start, end, &|tile| if passable(tile) { 1 } else { 0 })
+        }
+
+        let search = JumpPointSearch { map: self, passable, end: *end };
+        let (jump_points, cost) = astar(&(*start, (0, 0)),
+                                        |node| search.successors(node),
+                                        |(loc, _)| Movement::EIGHT_WAY.estimate(loc, end),
+                                        |(loc, _)| loc == end)?;
+
+        // Fill in the straight runs between each jump point.
+        let mut path = vec![*start];
+        for (to, direction) in jump_points.iter().skip(1) {
+            while path.last() != Some(to) {
+                path.push(add_delta(path.last().unwrap(), direction).unwrap());
+            }
+        }
+
+        Some((path, cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::map::generate_ascii_map;
+    use crate::{DijkstraMap, Map, PathAlgorithm, Spot};
+
+    fn available(tile: &char) -> usize {
+        if *tile == '.' { 1 } else { 0 }
+    }
+
+    fn assert_valid(map: &Map<char, char>, path: &[(usize, usize)]) {
+        for pair in path.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert!(a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1 && a != b, "{:?} -> {:?}", a, b);
+            assert_eq!(map.get(&b).unwrap().solid, '.');
+        }
+    }
+
+    #[test]
+    fn test_open_map() {
+        let map: Map<char, char> = Map::new("map", 20, 10, &|_| '.');
+        let (path, cost) = map.shortest_path_using(PathAlgorithm::JPS, &(1, 1), &(18, 5), &available).unwrap();
+
+        assert_eq!(cost, 17);
+        assert_eq!(path.len(), 18);
+        assert_eq!((path[0], path[17]), ((1, 1), (18, 5)));
+        assert_valid(&map, &path);
+    }
+
+    #[test]
+    fn test_around_walls() {
+        let map = generate_ascii_map("map", "..........\n\
+                                              .######...\n\
+                                              ......#...\n\
+                                              ####..#...\n\
+                                              ......#...\n").unwrap();
+        let (path, cost) = map.shortest_path_using(PathAlgorithm::JPS, &(0, 4), &(7, 4), &available).unwrap();
+
+        assert_eq!(cost, 18);
+        assert_eq!(path.first(), Some(&(0, 4)));
+        assert_eq!(path.last(), Some(&(7, 4)));
+        assert_valid(&map, &path);
+        assert!(map.shortest_path_using(PathAlgorithm::JPS, &(0, 4), &(2, 1), &available).is_none());
+    }
+
+    #[test]
+    fn test_same_cost_as_optimal() {
+        let mut rng = StdRng::seed_from_u64(11);
+
+        for _ in 0..20 {
+            let mut map: Map<char, char> = Map::new("map", 30, 30, &|_| '.');
+            for _ in 0..250 {
+                map.set(&(rng.gen_range(0..30), rng.gen_range(0..30)), Spot::new('#', None));
+            }
+            let (start, end) = ((0, 0), (29, 29));
+            map.set(&start, Spot::new('.', None));
+            map.set(&end, Spot::new('.', None));
+
+            let distances = DijkstraMap::new(&map, &[end], &available);
+            match map.shortest_path_using(PathAlgorithm::JPS, &start, &end, &available) {
+                Some((path, cost)) => {
+                    assert_eq!(cost as f32, distances.get(start).unwrap());
+                    assert_eq!(path.len(), cost + 1);
+                    assert_valid(&map, &path);
+                }
+                None => assert!(!distances.is_reachable(start)),
+            }
+        }
+    }
+}
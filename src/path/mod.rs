@@ -0,0 +1,33 @@
+use crate::Map;
+
+mod bounded;
+mod hpa;
+mod jps;
+mod movement;
+mod smooth;
+
+pub use bounded::{PartialPath, SearchLimit};
+pub use hpa::HierarchicalMap;
+pub use movement::{Heuristic, Movement};
+
+/// Search used by Map::shortest_path_using.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathAlgorithm {
+    /// A* honouring the cost available gives each tile.
+    ASTAR,
+    /// Jump Point Search.  Much faster on large open maps but every available tile costs 1 (any
+    /// non-zero cost from available is treated as 1).
+    JPS,
+}
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// Same as shortest_path but with a choice of search.  Returns the path (start and end
+    /// included) and its cost.
+    pub fn shortest_path_using(&self, algorithm: PathAlgorithm, start: &(usize, usize), end: &(usize, usize),
+                               available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
+        match algorithm {
+            PathAlgorithm::ASTAR => self.shortest_path(start, end, available),
+            PathAlgorithm::JPS => self.jump_point_search(start, end, &|tile| available(tile) != 0),
+        }
+    }
+}
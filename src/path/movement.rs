@@ -0,0 +1,231 @@
+use pathfinding::prelude::astar;
+use crate::{Error, Map};
+
+const STRAIGHT: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// Estimate of the cost between two locations which never overestimates for the movement it
+/// belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heuristic {
+    /// Sum of the distances along each axis for straight moves only.
+    MANHATTAN,
+    /// Longest distance along either axis when diagonals cost the same as straight moves.
+    CHEBYSHEV,
+    /// Diagonal moves for the shorter axis and straight moves for the rest when diagonals cost
+    /// a different amount than straight moves.
+    OCTILE,
+}
+
+/// How a path may move from one location to the next.  The cost of a step is the cost available
+/// gives the tile entered multiplied by straight_cost or diagonal_cost.  Those are only set by
+/// the constants and with_diagonal_cost so they are never 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Movement {
+    pub diagonals: bool,
+    /// Whether a diagonal step may squeeze past a blocked tile beside it.
+    pub corner_cutting: bool,
+    straight_cost: usize,
+    diagonal_cost: usize,
+}
+
+impl Movement {
+    /// Up, down, left and right only.
+    pub const FOUR_WAY: Movement = Movement { diagonals: false, corner_cutting: false, straight_cost: 1, diagonal_cost: 1 };
+    /// Diagonals cost the same as any other step and may slip between two blocked tiles touching
+    /// at a corner (same as Map::shortest_path).
+    pub const EIGHT_WAY: Movement = Movement { diagonals: true, corner_cutting: true, straight_cost: 1, diagonal_cost: 1 };
+    /// Diagonal steps need both tiles beside them to be available.
+    pub const NO_CORNER_CUTTING: Movement = Movement { diagonals: true, corner_cutting: false, straight_cost: 1, diagonal_cost: 1 };
+    /// No corner cutting and diagonals cost 1.4 times as much (costs are multiplied by 5).
+    pub const OCTILE: Movement = Movement { diagonals: true, corner_cutting: false, straight_cost: 5, diagonal_cost: 7 };
+
+    /// Same movement but diagonals cost diagonal_cost for every straight_cost of a straight
+    /// step (e.g. 10 and 14).  InvalidArgument if either is 0.
+    pub fn with_diagonal_cost(mut self, straight_cost: usize, diagonal_cost: usize) -> Result<Self, Error> {
+        if straight_cost == 0 || diagonal_cost == 0 {
+            return Err(Error::InvalidArgument(format!("step costs must be at least 1 (got {} and {})", straight_cost, diagonal_cost)))
+        }
+
+        self.straight_cost = straight_cost;
+        self.diagonal_cost = diagonal_cost;
+        Ok(self)
+    }
+
+    pub fn straight_cost(&self) -> usize {
+        self.straight_cost
+    }
+
+    pub fn diagonal_cost(&self) -> usize {
+        self.diagonal_cost
+    }
+
+    pub fn heuristic(&self) -> Heuristic {
+        if !self.diagonals {
+            Heuristic::MANHATTAN
+        } else if self.diagonal_cost == self.straight_cost {
+            Heuristic::CHEBYSHEV
+        } else {
+            Heuristic::OCTILE
+        }
+    }
+
+    /// Cheapest possible cost between a and b assuming every tile costs at least 1.
+    pub fn estimate(&self, a: &(usize, usize), b: &(usize, usize)) -> usize {
+        self.estimate_delta(a.0.abs_diff(b.0), a.1.abs_diff(b.1))
+    }
+
+    /// Same as estimate but going across the edges of a map which wraps when that is shorter.
+    pub fn estimate_on<T: PartialEq, I: Default + PartialEq>(&self, map: &Map<T, I>, a: &(usize, usize), b: &(usize, usize)) -> usize {
+        let (dx, dy) = map.delta(a, b);
+
+        self.estimate_delta(dx.unsigned_abs(), dy.unsigned_abs())
+    }
+
+    fn estimate_delta(&self, dx: usize, dy: usize) -> usize {
+        let (short, long) = (dx.min(dy), dx.max(dy));
+
+        match self.heuristic() {
+            Heuristic::MANHATTAN => (dx + dy) * self.straight_cost,
+            Heuristic::CHEBYSHEV => long * self.straight_cost,
+            // A diagonal costing more than two straight steps is never used and one costing less
+            // than a straight step makes zigzagging diagonals cheaper than going straight.
+            Heuristic::OCTILE => short * self.diagonal_cost.min(2 * self.straight_cost) +
+                (long - short) * self.straight_cost.min(self.diagonal_cost),
+        }
+    }
+
+    /// Locations which can be stepped to from loc along with the cost of the step.
+    pub fn neighbors<T: PartialEq, I: Default + PartialEq>(&self, map: &Map<T, I>, loc: &(usize, usize),
+                                                           available: &dyn Fn(&T) -> usize) -> Vec<((usize, usize), usize)> {
+        let cost = |delta: &(isize, isize)| {
+            map.offset(loc, delta)
+                .map(|next| (next, available(&map.get(&next).unwrap().solid)))
+                .filter(|(_, cost)| *cost != 0)
+        };
+
+        let mut neighbors: Vec<((usize, usize), usize)> = STRAIGHT.iter()
+            .filter_map(|delta| cost(delta).map(|(next, cost)| (next, cost * self.straight_cost)))
+            .collect();
+
+        if self.diagonals {
+            for delta in DIAGONAL.iter() {
+                if !self.corner_cutting && (cost(&(delta.0, 0)).is_none() || cost(&(0, delta.1)).is_none()) {
+                    continue
+                }
+
+                if let Some((next, cost)) = cost(delta) {
+                    neighbors.push((next, cost * self.diagonal_cost));
+                }
+            }
+        }
+
+        neighbors
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// A* moving by the given rules with the heuristic which suits them.
+    pub fn shortest_path_with(&self, movement: &Movement, start: &(usize, usize), end: &(usize, usize),
+                              available: &dyn Fn(&T) -> usize) -> Option<(Vec<(usize, usize)>, usize)> {
+        astar(start,
+              |loc| movement.neighbors(self, loc, available),
+              |loc| movement.estimate_on(self, loc, end),
+              |loc| loc == end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::generate_ascii_map;
+    use crate::path::movement::{Heuristic, Movement};
+
+    const MAP: &str = "......\n\
+                       .#....\n\
+                       ..#...\n\
+                       ......\n";
+
+    fn available(tile: &char) -> usize {
+        if *tile == '.' { 1 } else { 0 }
+    }
+
+    #[test]
+    fn test_heuristics() {
+        assert_eq!(Movement::FOUR_WAY.heuristic(), Heuristic::MANHATTAN);
+        assert_eq!(Movement::EIGHT_WAY.heuristic(), Heuristic::CHEBYSHEV);
+        assert_eq!(Movement::NO_CORNER_CUTTING.heuristic(), Heuristic::CHEBYSHEV);
+        assert_eq!(Movement::OCTILE.heuristic(), Heuristic::OCTILE);
+
+        assert_eq!(Movement::FOUR_WAY.estimate(&(0, 0), &(3, 5)), 8);
+        assert_eq!(Movement::EIGHT_WAY.estimate(&(0, 0), &(3, 5)), 5);
+        assert_eq!(Movement::OCTILE.estimate(&(0, 0), &(3, 5)), 3 * 7 + 2 * 5);
+        assert_eq!(Movement::EIGHT_WAY.with_diagonal_cost(1, 3).unwrap().estimate(&(0, 0), &(2, 2)), 4);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let locs = |movement: Movement| {
+            let mut locs: Vec<(usize, usize)> = movement.neighbors(&map, &(1, 2), &available).into_iter().map(|(loc, _)| loc).collect();
+            locs.sort();
+            locs
+        };
+
+        assert_eq!(locs(Movement::FOUR_WAY), vec![(0, 2), (1, 3)]);
+        assert_eq!(locs(Movement::EIGHT_WAY), vec![(0, 1), (0, 2), (0, 3), (1, 3), (2, 1), (2, 3)]);
+        assert_eq!(locs(Movement::NO_CORNER_CUTTING), vec![(0, 2), (0, 3), (1, 3)]);
+    }
+
+    #[test]
+    fn test_corner_cutting() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+
+        // Squeezing between (1, 1) and (2, 2) is only allowed with corner cutting.
+        let (path, cost) = map.shortest_path_with(&Movement::EIGHT_WAY, &(1, 2), &(2, 1), &available).unwrap();
+        assert_eq!((path, cost), (vec![(1, 2), (2, 1)], 1));
+
+        let (path, cost) = map.shortest_path_with(&Movement::NO_CORNER_CUTTING, &(1, 2), &(2, 1), &available).unwrap();
+        assert_eq!((path.len(), cost), (7, 6));
+        assert!(!path.windows(2).any(|step| step == [(1, 0), (2, 1)] || step == [(3, 2), (2, 1)]));
+
+        let (_, cost) = map.shortest_path_with(&Movement::FOUR_WAY, &(1, 2), &(2, 1), &available).unwrap();
+        assert_eq!(cost, 6);
+    }
+
+    #[test]
+    fn test_diagonal_cost() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+
+        let (path, cost) = map.shortest_path_with(&Movement::OCTILE, &(3, 0), &(5, 2), &available).unwrap();
+        assert_eq!((path.len(), cost), (3, 14));
+
+        // Diagonals costing more than two straight steps are never taken.
+        let expensive = Movement::NO_CORNER_CUTTING.with_diagonal_cost(1, 3).unwrap();
+        let (path, cost) = map.shortest_path_with(&expensive, &(3, 0), &(5, 2), &available).unwrap();
+        assert_eq!((path.len(), cost), (5, 4));
+    }
+
+    #[test]
+    fn test_cheap_diagonals() {
+        let map = generate_ascii_map("map", "......\n......\n").unwrap();
+        let cheap = Movement::EIGHT_WAY.with_diagonal_cost(10, 5).unwrap();
+
+        // Zigzagging diagonally beats walking straight so the estimate must allow for it.
+        let (_, cost) = map.shortest_path_with(&cheap, &(0, 0), &(4, 0), &available).unwrap();
+        assert_eq!(cost, 20);
+        assert!(cheap.estimate(&(0, 0), &(4, 0)) <= cost);
+
+        assert!(Movement::EIGHT_WAY.with_diagonal_cost(0, 1).is_err());
+        assert!(Movement::EIGHT_WAY.with_diagonal_cost(1, 0).is_err());
+    }
+
+    #[test]
+    fn test_eight_way_matches_shortest_path() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+
+        for end in [(5, 3), (0, 3), (2, 1)] {
+            let expected = map.shortest_path(&(0, 0), &end, &available).unwrap().1;
+            assert_eq!(map.shortest_path_with(&Movement::EIGHT_WAY, &(0, 0), &end, &available).unwrap().1, expected);
+        }
+    }
+}
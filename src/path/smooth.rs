@@ -0,0 +1,131 @@
+use crate::{BresenhamLine, Map, SupercoverLine};
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// Pull the string tight on a path (e.g. from shortest_path) so only the waypoints where it
+    /// has to turn are left.  A waypoint is skipped when the straight line past it touches
+    /// nothing impassable and walking it (see expand_path) costs no more than the original steps.
+    pub fn smooth_path(&self, path: &[(usize, usize)], available: &dyn Fn(&T) -> usize) -> Vec<(usize, usize)> {
+        if path.len() <= 2 {
+            return path.to_vec()
+        }
+
+        // Cost of walking the original path up to each step.
+        let mut costs = vec![0; path.len()];
+        for i in 1..path.len() {
+            costs[i] = costs[i - 1] + self.cost_at(&path[i], available);
+        }
+
+        let mut waypoints = vec![path[0]];
+        let mut anchor = 0;
+        for i in 2..path.len() {
+            if !self.is_shortcut(&path[anchor], &path[i], costs[i] - costs[anchor], available) {
+                anchor = i - 1;
+                waypoints.push(path[anchor]);
+            }
+        }
+        waypoints.push(path[path.len() - 1]);
+
+        waypoints
+    }
+
+    /// Walk the straight line between each pair of waypoints giving every tile stepped on
+    /// (first waypoint included).
+    pub fn expand_path(&self, waypoints: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut path: Vec<(usize, usize)> = waypoints.first().into_iter().copied().collect();
+
+        for pair in waypoints.windows(2) {
+            let (dx, dy) = self.delta(&pair[0], &pair[1]);
+
+            // A single step across the edge of a wrapping map.
+            if dx.abs() <= 1 && dy.abs() <= 1 {
+                path.push(pair[1]);
+            } else {
+                path.extend(BresenhamLine::new(pair[0], pair[1]).skip(1));
+            }
+        }
+
+        path
+    }
+
+    fn is_shortcut(&self, start: &(usize, usize), end: &(usize, usize), budget: usize, available: &dyn Fn(&T) -> usize) -> bool {
+        // Supercover so the line cannot slip between two walls touching at a corner.
+        SupercoverLine::new(*start, *end).all(|loc| self.cost_at(&loc, available) != 0) &&
+            BresenhamLine::new(*start, *end).skip(1).map(|loc| self.cost_at(&loc, available)).sum::<usize>() <= budget
+    }
+
+    #[inline]
+    fn cost_at(&self, loc: &(usize, usize), available: &dyn Fn(&T) -> usize) -> usize {
+        self.get(loc).map_or(0, |spot| available(&spot.solid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::generate_ascii_map;
+
+    const MAP: &str = "..........\n\
+                       ..........\n\
+                       ....#.....\n\
+                       ....#.....\n\
+                       ....#.....\n";
+
+    fn available(tile: &char) -> usize {
+        match tile {
+            '.' => 1,
+            '~' => 5,
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_open_floor() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let zig_zag = vec![(0, 0), (1, 1), (2, 1), (3, 0), (4, 1), (5, 1), (6, 1), (7, 0)];
+
+        assert_eq!(map.smooth_path(&zig_zag, &available), vec![(0, 0), (7, 0)]);
+        assert_eq!(map.smooth_path(&[(0, 0), (1, 1)], &available), vec![(0, 0), (1, 1)]);
+        assert_eq!(map.smooth_path(&[], &available), vec![]);
+    }
+
+    #[test]
+    fn test_around_wall() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let (path, cost) = map.shortest_path(&(2, 4), &(7, 4), &available).unwrap();
+        let waypoints = map.smooth_path(&path, &available);
+
+        assert!(waypoints.len() < path.len());
+        assert_eq!((waypoints.first(), waypoints.last()), (Some(&(2, 4)), Some(&(7, 4))));
+
+        let steps = map.expand_path(&waypoints);
+        assert!(steps.windows(2).all(|pair| pair[0].0.abs_diff(pair[1].0) <= 1 && pair[0].1.abs_diff(pair[1].1) <= 1));
+        assert!(steps.iter().all(|loc| map.get(loc).unwrap().solid == '.'));
+        assert!(steps.len() - 1 <= cost);
+    }
+
+    #[test]
+    fn test_corners() {
+        let map = generate_ascii_map("map", "....\n\
+                                            .#..\n\
+                                            ..#.\n\
+                                            ....\n").unwrap();
+
+        // Line from (0, 2) to (1, 0) touches the wall at (1, 1).
+        let path = vec![(0, 2), (0, 1), (0, 0), (1, 0), (2, 0)];
+        assert_eq!(map.smooth_path(&path, &available), vec![(0, 2), (0, 0), (2, 0)]);
+
+        // Squeezing between (1, 1) and (2, 2) is never a shortcut.
+        let path = vec![(1, 2), (1, 3), (2, 3), (3, 3), (3, 2), (3, 1), (2, 1)];
+        assert!(map.smooth_path(&path, &available).len() > 2);
+    }
+
+    #[test]
+    fn test_cost() {
+        let map = generate_ascii_map("map", "....\n\
+                                            .~~.\n\
+                                            ....\n").unwrap();
+
+        // Going straight through mud costs more than going around it.
+        let path = vec![(0, 1), (1, 0), (2, 0), (3, 1)];
+        assert_eq!(map.smooth_path(&path, &available), vec![(0, 1), (2, 0), (3, 1)]);
+    }
+}
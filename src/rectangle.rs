@@ -1,18 +1,20 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use crate::Error;
 use crate::rectangle::RectangleIteratorType::{BODY, BORDER};
 
 /// Rectangle with a single width border.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rectangle {
     pub ulc: (usize, usize),
     pub lrc: (usize, usize),
 }
 
 impl Rectangle {
-    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Result<Self, String> {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Result<Self, Error> {
         // We need a minimum of a wall on each side of the room but the room may have no
         // open spaces.
         if width < 2 || height < 2 {
-            return Err("Rectangle dimensions too small".to_string())
+            return Err(Error::InvalidDimensions("rectangle must be at least 2x2".to_string()))
         }
 
         Ok(Rectangle {
@@ -37,9 +39,7 @@ impl Rectangle {
     /// Give us a random valid x coordinate in/on this rectangle.
     /// If include_wall is true it will include the edges as a valid x
     /// value.  If not it will only be the body of the rectangle.
-    pub fn random_x(&self, include_wall: bool) -> usize {
-        let mut rng = thread_rng();
-
+    pub fn random_x<R: Rng + ?Sized>(&self, rng: &mut R, include_wall: bool) -> usize {
         let (start, end) = if include_wall {
             (0, self.lrc.0 - self.ulc.0)
         } else {
@@ -52,9 +52,7 @@ impl Rectangle {
     /// Give us a random valid x coordinate in/on this rectangle.
     /// If include_wall is true it will include the edges as a valid y
     /// value.  If not it will only be the body of the rectangle.
-    pub fn random_y(&self, include_wall: bool) -> usize {
-        let mut rng = thread_rng();
-
+    pub fn random_y<R: Rng + ?Sized>(&self, rng: &mut R, include_wall: bool) -> usize {
         let (start, end) = if include_wall {
             (0, self.lrc.1 - self.ulc.1)
         } else {
@@ -122,6 +120,8 @@ impl Iterator for RectangleIterator {
 
 #[cfg(test)]
 mod tests {
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, SeedableRng};
     use crate::rectangle::Rectangle;
     use crate::rectangle::RectangleIteratorType::BORDER;
 
@@ -175,6 +175,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::manual_range_contains)]
     fn test_rand_x() {
         //   012345
         // 0 .......
@@ -183,13 +184,14 @@ mod tests {
         // 3 .####..
         let rect = Rectangle::new(1, 1, 4, 3).unwrap();
 
-        let mut x = rect.random_x(true);
+        let mut x = rect.random_x(&mut thread_rng(), true);
         assert!(x >= 1 && x < 5);
-        x = rect.random_x(false);
+        x = rect.random_x(&mut thread_rng(), false);
         assert!(x > 1 && x < 4);
     }
 
     #[test]
+    #[allow(clippy::manual_range_contains)]
     fn test_rand_y() {
         //   012345
         // 0 .......
@@ -198,9 +200,21 @@ mod tests {
         // 3 .####..
         let rect = Rectangle::new(1, 1, 4, 3).unwrap();
 
-        let mut y = rect.random_y(true);
+        let mut y = rect.random_y(&mut thread_rng(), true);
         assert!(y >= 1 && y < 5);
-        y = rect.random_y(false);
+        y = rect.random_y(&mut thread_rng(), false);
         assert!(y > 1 && y < 4);
     }
+
+    #[test]
+    fn test_rand_seeded() {
+        let rect = Rectangle::new(3, 7, 20, 30).unwrap();
+        let mut rng1 = StdRng::seed_from_u64(42);
+        let mut rng2 = StdRng::seed_from_u64(42);
+
+        for _ in 0..20 {
+            assert_eq!(rect.random_x(&mut rng1, true), rect.random_x(&mut rng2, true));
+            assert_eq!(rect.random_y(&mut rng1, false), rect.random_y(&mut rng2, false));
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,168 @@
+use crate::{Map, Overlay, Rectangle};
+
+/// Label given to any location which is not passable (and so belongs to no region).
+pub const NO_REGION: usize = usize::MAX;
+
+/// Result of labeling all connected passable areas of a map.  Labels index into sizes and
+/// bounds.  Bounds are the tightest box around the region so unlike rooms they may be a single
+/// tile wide.
+pub struct Regions {
+    pub labels: Overlay<usize>,
+    pub sizes: Vec<usize>,
+    pub bounds: Vec<Rectangle>,
+}
+
+impl Regions {
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn label(&self, loc: (usize, usize)) -> Option<usize> {
+        self.labels.get(loc).copied().filter(|label| *label != NO_REGION)
+    }
+
+    pub fn largest(&self) -> Option<usize> {
+        self.sizes.iter().enumerate().max_by_key(|(_, size)| **size).map(|(label, _)| label)
+    }
+}
+
+impl<T: PartialEq, I: Default + PartialEq> Map<T, I> {
+    /// All locations reachable from start moving only through passable tiles.  start itself
+    /// must be passable or nothing is reachable.
+    pub fn flood_fill(&self, start: &(usize, usize), passable: &dyn Fn(&T) -> bool, include_diagonals: bool) -> Overlay<bool> {
+        let mut reachable = self.create_overlay();
+
+        if self.get(start).is_some_and(|spot| passable(&spot.solid)) {
+            self.fill(start, passable, include_diagonals, &mut reachable);
+        }
+
+        reachable
+    }
+
+    /// Label every connected area of passable tiles.  Labels are handed out in map iteration
+    /// order so the region containing the first passable tile is always 0.
+    pub fn label_regions(&self, passable: &dyn Fn(&T) -> bool, include_diagonals: bool) -> Regions {
+        let mut regions = Regions {
+            labels: Overlay::new(self.width, self.height, NO_REGION),
+            sizes: vec![],
+            bounds: vec![],
+        };
+        let mut seen = self.create_overlay();
+
+        for (loc, spot) in self.iter() {
+            if !passable(&spot.solid) || *seen.get(loc).unwrap() {
+                continue
+            }
+
+            let label = regions.count();
+            let region = self.fill(&loc, passable, include_diagonals, &mut seen);
+            let (mut ulc, mut lrc) = (loc, loc);
+            for current in region.iter() {
+                regions.labels.set(*current, label);
+                ulc = (ulc.0.min(current.0), ulc.1.min(current.1));
+                lrc = (lrc.0.max(current.0), lrc.1.max(current.1));
+            }
+
+            regions.sizes.push(region.len());
+            regions.bounds.push(Rectangle { ulc, lrc });
+        }
+
+        regions
+    }
+
+    // Depth first fill from start marking everything found in seen.  Returns the locations found.
+    fn fill(&self, start: &(usize, usize), passable: &dyn Fn(&T) -> bool, include_diagonals: bool,
+            seen: &mut Overlay<bool>) -> Vec<(usize, usize)> {
+        let mut found = vec![*start];
+        let mut stack = vec![*start];
+        seen.set(*start, true);
+
+        while let Some(current) = stack.pop() {
+            for next in self.adjacent_matching(&current, passable, include_diagonals) {
+                if !*seen.get(next).unwrap() {
+                    seen.set(next, true);
+                    found.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::generate_ascii_map;
+    use crate::region::NO_REGION;
+    use crate::{Spot, Wrap};
+
+    const MAP: &str = "#######\n\
+                       #..#..#\n\
+                       #..#..#\n\
+                       ###.###\n\
+                       #.#..##\n\
+                       #######";
+
+    #[test]
+    fn test_flood_fill() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let passable = |tile: &char| *tile == '.';
+
+        let reachable = map.flood_fill(&(1, 1), &passable, false);
+        assert_eq!(reachable.iter().filter(|(_, r)| **r).count(), 4);
+        assert!(!reachable.get((4, 1)).unwrap());
+
+        let reachable = map.flood_fill(&(1, 1), &passable, true);
+        assert_eq!(reachable.iter().filter(|(_, r)| **r).count(), 11);
+        assert!(!reachable.get((1, 4)).unwrap());
+
+        let reachable = map.flood_fill(&(0, 0), &passable, true);
+        assert_eq!(reachable.iter().filter(|(_, r)| **r).count(), 0);
+    }
+
+    #[test]
+    fn test_label_regions_4_way() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let regions = map.label_regions(&|tile: &char| *tile == '.', false);
+
+        assert_eq!(regions.count(), 4);
+        assert_eq!(regions.sizes, vec![4, 4, 3, 1]);
+        assert_eq!(regions.label((2, 2)), Some(0));
+        assert_eq!(regions.label((0, 0)), None);
+        assert_eq!(regions.labels.get((0, 0)), Some(&NO_REGION));
+        assert_eq!(regions.label((4, 4)), Some(2));
+        assert_eq!(regions.label((1, 4)), Some(3));
+        assert_eq!((regions.bounds[1].ulc, regions.bounds[1].lrc), ((4, 1), (5, 2)));
+        assert_eq!((regions.bounds[2].ulc, regions.bounds[2].lrc), ((3, 3), (4, 4)));
+        assert_eq!((regions.bounds[3].ulc, regions.bounds[3].lrc), ((1, 4), (1, 4)));
+    }
+
+    #[test]
+    fn test_label_regions_8_way() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let regions = map.label_regions(&|tile: &char| *tile == '.', true);
+
+        assert_eq!(regions.count(), 2);
+        assert_eq!(regions.sizes, vec![11, 1]);
+        assert_eq!(regions.largest(), Some(0));
+        assert_eq!((regions.bounds[0].ulc, regions.bounds[0].lrc), ((1, 1), (5, 4)));
+        assert_eq!(regions.label((1, 4)), Some(1));
+    }
+
+    #[test]
+    fn test_flood_fill_wrap() {
+        let mut map = generate_ascii_map("map", MAP).unwrap();
+        map.set(&(1, 0), Spot::new('.', None));
+        map.set(&(1, 5), Spot::new('.', None));
+        let passable = |tile: &char| *tile == '.';
+
+        assert_eq!(map.flood_fill(&(1, 4), &passable, false).iter().filter(|(_, r)| **r).count(), 2);
+
+        // Out the bottom and back in through the top.
+        let map = map.with_wrap(Wrap::VERTICAL);
+        let reachable = map.flood_fill(&(1, 4), &passable, false);
+        assert_eq!(reachable.iter().filter(|(_, r)| **r).count(), 7);
+        assert!(*reachable.get((2, 2)).unwrap());
+    }
+}
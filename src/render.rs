@@ -0,0 +1,128 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use crate::{Map, Overlay};
+
+/// Text renderer for a map.  Each location is drawn by the first of these which applies:
+///
+///   1. mask: hidden glyph when the mask is false at that location (e.g. outside of FOV)
+///   2. layer: glyph for the most recently added layer which is true at that location (e.g. a path)
+///   3. items: glyph for the first item on the spot
+///   4. glyph_fn for the solid
+pub struct MapRenderer<'a, T: PartialEq, I: Default + PartialEq> {
+    map: &'a Map<T, I>,
+    glyph_fn: &'a dyn Fn(&T) -> char,
+    item_fn: Option<&'a dyn Fn(&I) -> char>,
+    layers: Vec<(&'a Overlay<bool>, char)>,
+    mask: Option<(&'a Overlay<bool>, char)>,
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> MapRenderer<'a, T, I> {
+    pub fn new(map: &'a Map<T, I>, glyph_fn: &'a dyn Fn(&T) -> char) -> Self {
+        Self {
+            map,
+            glyph_fn,
+            item_fn: None,
+            layers: vec![],
+            mask: None,
+        }
+    }
+
+    pub fn items(mut self, item_fn: &'a dyn Fn(&I) -> char) -> Self {
+        self.item_fn = Some(item_fn);
+        self
+    }
+
+    pub fn layer(mut self, overlay: &'a Overlay<bool>, glyph: char) -> Self {
+        self.layers.push((overlay, glyph));
+        self
+    }
+
+    pub fn mask(mut self, overlay: &'a Overlay<bool>, hidden: char) -> Self {
+        self.mask = Some((overlay, hidden));
+        self
+    }
+
+    pub fn glyph_at(&self, loc: (usize, usize)) -> Option<char> {
+        let spot = self.map.get(&loc)?;
+
+        if let Some((mask, hidden)) = self.mask {
+            if mask.get(loc) == Some(&false) {
+                return Some(hidden)
+            }
+        }
+
+        if let Some((_, glyph)) = self.layers.iter().rev().find(|(layer, _)| layer.get(loc) == Some(&true)) {
+            return Some(*glyph)
+        }
+
+        if let (Some(item_fn), Some(items)) = (self.item_fn, &spot.items) {
+            if let Some((item, _)) = items.first() {
+                return Some(item_fn(item))
+            }
+        }
+
+        Some((self.glyph_fn)(&spot.solid))
+    }
+}
+
+impl<'a, T: PartialEq, I: Default + PartialEq> Display for MapRenderer<'a, T, I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for y in 0..self.map.height {
+            let line: String = (0..self.map.width).filter_map(|x| self.glyph_at((x, y))).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::generate_ascii_map;
+    use crate::{calculate_field_of_view, MapRenderer, Spot};
+
+    const MAP: &str = "#####\n\
+                       #...#\n\
+                       #.#.#\n\
+                       #####\n";
+
+    #[test]
+    fn test_render_glyphs() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let glyph = |tile: &char| if *tile == '#' { '█' } else { ' ' };
+
+        assert_eq!(MapRenderer::new(&map, &glyph).to_string(), "█████\n█   █\n█ █ █\n█████\n");
+        assert_eq!(format!("{}", map), MAP);
+    }
+
+    #[test]
+    fn test_render_items_and_layers() {
+        let mut map = generate_ascii_map("map", MAP).unwrap();
+        map.get_mut(&(3, 1)).unwrap().add_item(('$', 1));
+        let available = |tile: &char| if *tile == '.' { 1 } else { 0 };
+        let (path, _) = map.shortest_path(&(1, 2), &(3, 2), &available).unwrap();
+        let mut highlight = map.create_overlay();
+        for loc in path {
+            highlight.set(loc, true);
+        }
+
+        let glyph = |tile: &char| *tile;
+        let item = |item: &char| *item;
+        let renderer = MapRenderer::new(&map, &glyph).items(&item).layer(&highlight, '*');
+
+        assert_eq!(renderer.to_string(), "#####\n#.*$#\n#*#*#\n#####\n");
+    }
+
+    #[test]
+    fn test_render_mask() {
+        let map = generate_ascii_map("map", MAP).unwrap();
+        let mut fov = map.create_overlay();
+        calculate_field_of_view(&map, &(1, 2), 2, &mut fov, &|spot: &Spot<char, char>| spot.solid == '.');
+
+        let glyph = |tile: &char| *tile;
+        let renderer = MapRenderer::new(&map, &glyph).mask(&fov, ' ');
+
+        assert_eq!(renderer.glyph_at((1, 2)), Some('.'));
+        assert_eq!(renderer.glyph_at((4, 0)), Some(' '));
+        assert_eq!(renderer.glyph_at((5, 0)), None);
+    }
+}
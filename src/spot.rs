@@ -1,5 +1,6 @@
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Spot<T: PartialEq, I: Default + PartialEq> {
     pub solid: T,
     pub items: Option<Vec<(I, usize)>>,
@@ -16,7 +17,7 @@ impl <T: PartialEq, I: Default + PartialEq> Spot<T, I> {
     pub fn add_item(&mut self, item: (I, usize)) {
         if let Some(items) = &mut self.items {
             if let Some(index) = items.iter().position(|(r, _)| r == &item.0) {
-                items[index].1 = items[index].1 + item.1;
+                items[index].1 += item.1;
             } else {
                 items.push(item);
             }